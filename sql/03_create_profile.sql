@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS Profile (
+    id BIGINT GENERATED ALWAYS AS IDENTITY,
+    first_seen_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    did TEXT UNIQUE,
+    display_name TEXT,
+    description TEXT,
+    details_updated_at TIMESTAMP WITH TIME ZONE,
+    has_been_processed BOOLEAN DEFAULT FALSE,
+    likely_country_of_living TEXT
+);
+
+ALTER TABLE Post ADD COLUMN author_did TEXT;
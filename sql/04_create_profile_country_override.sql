@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS ProfileCountryOverride (
+    id BIGINT GENERATED ALWAYS AS IDENTITY,
+    did TEXT UNIQUE NOT NULL,
+    country TEXT NOT NULL,
+    set_by TEXT NOT NULL,
+    set_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS FeedPost (
+    id BIGINT GENERATED ALWAYS AS IDENTITY,
+    feed TEXT NOT NULL,
+    uri TEXT NOT NULL REFERENCES Post (uri) ON DELETE CASCADE,
+    CONSTRAINT feed_uri_unique UNIQUE (feed, uri)
+);
+
+-- Everything indexed so far was indexed for the only feed there was
+INSERT INTO FeedPost (feed, uri) SELECT 'nederlandskie', uri FROM Post WHERE uri IS NOT NULL;
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS ListItem (
+    id BIGINT GENERATED ALWAYS AS IDENTITY,
+    uri TEXT UNIQUE NOT NULL,
+    list_uri TEXT NOT NULL,
+    subject_did TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS list_item_list_uri_subject_did ON ListItem (list_uri, subject_did);
@@ -0,0 +1,9 @@
+ALTER TABLE Post ADD COLUMN IF NOT EXISTS like_count INT NOT NULL DEFAULT 0;
+ALTER TABLE Post ADD COLUMN IF NOT EXISTS repost_count INT NOT NULL DEFAULT 0;
+
+CREATE TABLE IF NOT EXISTS PostEngagement (
+    id BIGINT GENERATED ALWAYS AS IDENTITY,
+    uri TEXT UNIQUE NOT NULL,
+    post_uri TEXT NOT NULL REFERENCES Post (uri) ON DELETE CASCADE,
+    kind TEXT NOT NULL
+);
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS Interaction (
+    id BIGINT GENERATED ALWAYS AS IDENTITY,
+    viewer_did TEXT NOT NULL,
+    post_uri TEXT NOT NULL,
+    event TEXT NOT NULL,
+    feed_context TEXT,
+    interacted_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    CONSTRAINT viewer_post_event_unique UNIQUE (viewer_did, post_uri, event)
+);
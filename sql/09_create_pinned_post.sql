@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS PinnedPost (
+    id BIGINT GENERATED ALWAYS AS IDENTITY,
+    feed TEXT NOT NULL,
+    uri TEXT NOT NULL,
+    pinned_by TEXT NOT NULL,
+    pinned_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    CONSTRAINT pinned_feed_uri_unique UNIQUE (feed, uri)
+);
@@ -0,0 +1,24 @@
+CREATE TABLE IF NOT EXISTS BannedAuthor (
+    id BIGINT GENERATED ALWAYS AS IDENTITY,
+    did TEXT UNIQUE NOT NULL,
+    reason TEXT NOT NULL,
+    banned_by TEXT NOT NULL,
+    banned_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS HiddenPost (
+    id BIGINT GENERATED ALWAYS AS IDENTITY,
+    uri TEXT UNIQUE NOT NULL,
+    reason TEXT NOT NULL,
+    hidden_by TEXT NOT NULL,
+    hidden_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS ModerationAction (
+    id BIGINT GENERATED ALWAYS AS IDENTITY,
+    action TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    reason TEXT,
+    performed_by TEXT NOT NULL,
+    performed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
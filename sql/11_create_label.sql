@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS Label (
+    id BIGINT GENERATED ALWAYS AS IDENTITY,
+    src TEXT NOT NULL,
+    uri TEXT NOT NULL,
+    val TEXT NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE,
+    CONSTRAINT label_src_uri_val_unique UNIQUE (src, uri, val)
+);
+
+CREATE INDEX IF NOT EXISTS label_uri_val ON Label (uri, val);
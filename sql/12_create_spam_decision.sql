@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS SpamDecision (
+    id BIGINT GENERATED ALWAYS AS IDENTITY,
+    did TEXT NOT NULL,
+    uri TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    details TEXT NOT NULL,
+    decided_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
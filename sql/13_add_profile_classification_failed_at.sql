@@ -0,0 +1,3 @@
+-- Profiles that couldn't be classified go to the back of the line, so that they
+-- don't keep the ones after them from being classified
+ALTER TABLE Profile ADD COLUMN IF NOT EXISTS classification_failed_at TIMESTAMP WITH TIME ZONE;
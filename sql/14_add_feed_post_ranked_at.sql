@@ -0,0 +1,14 @@
+-- Posts are served straight off an index in the order of their rank, which for
+-- feeds ranked by engagement gets updated whenever the engagement does
+ALTER TABLE FeedPost ADD COLUMN IF NOT EXISTS cid TEXT;
+ALTER TABLE FeedPost ADD COLUMN IF NOT EXISTS ranked_at TIMESTAMP WITH TIME ZONE;
+
+UPDATE FeedPost SET cid = Post.cid, ranked_at = Post.indexed_at
+FROM Post
+WHERE Post.uri = FeedPost.uri AND FeedPost.ranked_at IS NULL;
+
+ALTER TABLE FeedPost ALTER COLUMN ranked_at SET DEFAULT NOW();
+ALTER TABLE FeedPost ALTER COLUMN ranked_at SET NOT NULL;
+
+CREATE INDEX IF NOT EXISTS feed_post_ranked_at_index ON FeedPost (feed, ranked_at DESC, cid DESC);
+CREATE INDEX IF NOT EXISTS post_indexed_at_index ON Post (indexed_at DESC, cid DESC);
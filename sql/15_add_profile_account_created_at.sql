@@ -0,0 +1,3 @@
+-- Accounts never get any younger, so when they were created only has to be looked
+-- up in the PLC directory once
+ALTER TABLE Profile ADD COLUMN IF NOT EXISTS account_created_at TIMESTAMP WITH TIME ZONE;
@@ -1,30 +1,107 @@
+mod configured;
+mod cursor;
+mod labels;
 mod nederlandskie;
+pub mod query;
 
 use std::collections::HashMap;
+use std::path::PathBuf;
 
 use anyhow::Result;
 use async_trait::async_trait;
 use atrium_api::types::Collection;
-use chrono::{DateTime, Utc};
 
-use crate::services::database::{self, Database};
+use crate::config::Config;
+use crate::services::bluesky::{ContentMode, PostFeatures};
+use crate::services::database::{self, Database, FeedFilter, Ranking};
 
+pub use self::configured::ConfiguredAlgo;
+pub use self::cursor::{BadCursor, Cursor, SortKey};
+pub use self::labels::LabelPolicy;
 pub use self::nederlandskie::Nederlandskie;
 
+/// How a feed presents itself in Bluesky, published in its feed generator record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeedMetadata {
+    pub display_name: String,
+    pub description: Option<String>,
+    /// Path to the image shown as the avatar of the feed
+    pub avatar: Option<PathBuf>,
+    pub content_mode: ContentMode,
+    pub accepts_interactions: bool,
+}
+
+/// Names and metadata of every feed that we serve, without setting up their algos.
+pub fn registered_feeds(config: &Config) -> Vec<(String, FeedMetadata)> {
+    std::iter::once((Nederlandskie::NAME.to_owned(), Nederlandskie::metadata()))
+        .chain(
+            config
+                .feeds
+                .iter()
+                .map(|feed| (feed.name.clone(), ConfiguredAlgo::metadata(feed))),
+        )
+        .collect()
+}
+
+/// Why a post is shown in a feed, when it's not just for belonging in it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FeedReason {
+    /// The post is pinned to the top of the feed
+    Pin,
+}
+
+/// A post the way it's served in a feed.
+pub struct FeedItem {
+    pub post: database::Post,
+    /// Passed back to us along with interactions of viewers with the post, so that
+    /// they can be attributed to whatever got the post selected
+    pub feed_context: Option<String>,
+    pub reason: Option<FeedReason>,
+}
+
+impl From<database::Post> for FeedItem {
+    fn from(post: database::Post) -> Self {
+        Self {
+            post,
+            feed_context: None,
+            reason: None,
+        }
+    }
+}
+
 #[async_trait]
 pub trait Algo {
     async fn should_index_post(
         &self,
         author_did: &str,
         post: &<atrium_api::app::bsky::feed::Post as Collection>::Record,
+        features: &PostFeatures,
     ) -> Result<bool>;
 
+    /// Fetches a page of the feed, leaving out posts the way the given filter says.
     async fn fetch_posts(
         &self,
         database: &Database,
+        feed_name: &str,
         limit: u8,
-        earlier_than: Option<(DateTime<Utc>, &str)>,
-    ) -> Result<Vec<database::Post>>;
+        after: Option<&Cursor>,
+        filter: &FeedFilter<'_>,
+    ) -> Result<Vec<FeedItem>>;
+
+    /// Which posts to keep out of the feed, pinned ones included. The viewer is known
+    /// when the request was made on behalf of a logged in user, so that posts they
+    /// asked to see less of can be left out.
+    fn filter<'a>(&'a self, viewer_did: Option<&'a str>) -> FeedFilter<'a>;
+
+    /// Order in which posts of the feed are served.
+    fn ranking(&self) -> Ranking {
+        Ranking::Chronological
+    }
+
+    /// The cursor for the page that follows the given post.
+    fn cursor_for(&self, post: &database::Post) -> Cursor {
+        Cursor::time(post.ranked_at, &post.cid)
+    }
 }
 
 pub type AnyAlgo = Box<dyn Algo + Sync + Send>;
@@ -43,6 +120,10 @@ impl Algos {
         self.algos.values()
     }
 
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &AnyAlgo)> {
+        self.algos.iter().map(|(name, algo)| (name.as_str(), algo))
+    }
+
     pub fn get_by_name(&self, name: &str) -> Option<&AnyAlgo> {
         self.algos.get(name)
     }
@@ -58,6 +139,10 @@ impl AlgosBuilder {
         Default::default()
     }
 
+    pub fn contains(&self, name: &str) -> bool {
+        self.algos.contains_key(name)
+    }
+
     pub fn add<T: Algo + Send + Sync + 'static>(mut self, name: &str, algo: T) -> Self {
         self.algos.insert(name.to_owned(), Box::new(algo));
         self
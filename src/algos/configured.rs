@@ -0,0 +1,596 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use atrium_api::types::Collection;
+
+use super::query::{normalize_words, post_hashtags, Query, QueryContext};
+use super::{Algo, Cursor, FeedItem, FeedMetadata, LabelPolicy};
+
+use crate::config::{FeedConfig, FeedRanking};
+use crate::services::bluesky::PostFeatures;
+use crate::services::database::{Database, FeedFilter, Ranking};
+use crate::services::language::{parse_language_tag, LanguageFilter};
+use crate::services::{Bluesky, LanguageDetector, ListMembers};
+
+/// An algorithm defined in configuration rather than in code.
+///
+/// Everything except the countries and the labels from the labeler is checked when
+/// posts get indexed. Countries of authors are only known once their profiles have
+/// been classified, and labelers take their time too, so those are checked when
+/// posts are fetched.
+pub struct ConfiguredAlgo {
+    language_detector: Arc<LanguageDetector>,
+    language_filter: Option<LanguageFilter>,
+    countries: Option<Vec<String>>,
+    ranking: Ranking,
+    label_policy: LabelPolicy,
+    excluded_labels: Vec<String>,
+    /// Keywords are stored like [`normalize_words`] makes them, padded with spaces,
+    /// so that they only match whole words
+    include_keywords: Vec<String>,
+    include_hashtags: HashSet<String>,
+    exclude_keywords: Vec<String>,
+    exclude_hashtags: HashSet<String>,
+    query: Option<Query>,
+    list_uri: Option<String>,
+    list_members: Arc<ListMembers>,
+    allowed_authors: HashSet<String>,
+    denied_authors: HashSet<String>,
+    include_replies: bool,
+    min_language_confidence: f64,
+    min_text_length: usize,
+}
+
+impl ConfiguredAlgo {
+    /// Compiles the feed configuration, resolving the handles of allowed and denied authors.
+    pub async fn compile(
+        config: &FeedConfig,
+        publisher_did: &str,
+        language_detector: Arc<LanguageDetector>,
+        list_members: Arc<ListMembers>,
+        bluesky: &Bluesky,
+    ) -> Result<Self> {
+        let allowed_authors = resolve_authors(bluesky, &config.allowed_authors).await?;
+        let denied_authors = resolve_authors(bluesky, &config.denied_authors).await?;
+
+        Self::new(
+            config,
+            publisher_did,
+            allowed_authors,
+            denied_authors,
+            language_detector,
+            list_members,
+        )
+    }
+
+    pub fn metadata(config: &FeedConfig) -> FeedMetadata {
+        FeedMetadata {
+            display_name: config
+                .display_name
+                .clone()
+                .unwrap_or_else(|| config.name.clone()),
+            description: config.description.clone(),
+            avatar: config.avatar.clone(),
+            content_mode: config.content_mode,
+            accepts_interactions: config.accepts_interactions,
+        }
+    }
+
+    fn new(
+        config: &FeedConfig,
+        publisher_did: &str,
+        allowed_authors: HashSet<String>,
+        denied_authors: HashSet<String>,
+        language_detector: Arc<LanguageDetector>,
+        list_members: Arc<ListMembers>,
+    ) -> Result<Self> {
+        let languages = config
+            .languages
+            .iter()
+            .map(|tag| {
+                let language = parse_language_tag(tag)
+                    .ok_or_else(|| anyhow!("Feed {}: unknown language {tag}", config.name))?;
+
+                if !language_detector.candidates().contains(&language) {
+                    return Err(anyhow!(
+                        "Feed {}: language {tag} is not one of the detected languages",
+                        config.name
+                    ));
+                }
+
+                Ok(language)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let list_uri = config
+            .list
+            .as_deref()
+            .map(|list| {
+                let list_prefix = format!(
+                    "at://{publisher_did}/{}/",
+                    atrium_api::app::bsky::graph::List::NSID
+                );
+
+                if !list.starts_with("at://") {
+                    Ok(format!("{list_prefix}{list}"))
+                } else if list.starts_with(&list_prefix) {
+                    Ok(list.to_owned())
+                } else {
+                    Err(anyhow!(
+                        "Feed {}: list {list} is not a list owned by {publisher_did}",
+                        config.name
+                    ))
+                }
+            })
+            .transpose()?;
+
+        let query = config
+            .query
+            .as_deref()
+            .map(Query::parse)
+            .transpose()
+            .map_err(|e| anyhow!("Feed {}: invalid query: {e}", config.name))?;
+
+        let query_languages = query.iter().flat_map(Query::languages);
+        if let Some(language) = query_languages
+            .into_iter()
+            .find(|language| !language_detector.candidates().contains(language))
+        {
+            return Err(anyhow!(
+                "Feed {}: language {language} in the query is not one of the detected languages",
+                config.name
+            ));
+        }
+
+        let language_filter = (!languages.is_empty()).then_some(LanguageFilter {
+            languages,
+            min_confidence: config.min_language_confidence,
+            min_text_length: config.min_text_length,
+        });
+
+        let ranking = match config.ranking {
+            FeedRanking::Chronological => Ranking::Chronological,
+            FeedRanking::Hot => Ranking::Hot {
+                gravity: Duration::try_from_secs_f64(config.hot_gravity_hours * 3600.0)
+                    .ok()
+                    .filter(|gravity| !gravity.is_zero())
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "Feed {}: hot gravity must be a positive number of hours",
+                            config.name
+                        )
+                    })?,
+            },
+        };
+
+        let countries = (!config.countries.is_empty()).then(|| {
+            config
+                .countries
+                .iter()
+                .map(|country| country.to_lowercase())
+                .collect()
+        });
+
+        let label_policy = LabelPolicy::new(config.labels.clone());
+
+        Ok(Self {
+            language_detector,
+            language_filter,
+            countries,
+            ranking,
+            excluded_labels: label_policy.excluded_labels(),
+            label_policy,
+            include_keywords: normalize_keywords(&config.include_keywords),
+            include_hashtags: normalize_hashtags(&config.include_hashtags),
+            exclude_keywords: normalize_keywords(&config.exclude_keywords),
+            exclude_hashtags: normalize_hashtags(&config.exclude_hashtags),
+            query,
+            list_uri,
+            list_members,
+            allowed_authors,
+            denied_authors,
+            include_replies: config.include_replies,
+            min_language_confidence: config.min_language_confidence,
+            min_text_length: config.min_text_length,
+        })
+    }
+
+    fn accepts(&self, author_did: &str, features: &PostFeatures) -> bool {
+        if self.denied_authors.contains(author_did) {
+            return false;
+        }
+
+        if !self.allowed_authors.is_empty() && !self.allowed_authors.contains(author_did) {
+            return false;
+        }
+
+        if let Some(list_uri) = &self.list_uri {
+            if !self.list_members.contains(list_uri, author_did) {
+                return false;
+            }
+        }
+
+        if !self.include_replies && features.is_reply() {
+            return false;
+        }
+
+        if !self.label_policy.accepts_self_labels(&features.self_labels) {
+            return false;
+        }
+
+        let text = format!(" {} ", normalize_words(&features.all_text()));
+        let hashtags = post_hashtags(features);
+
+        let has_keyword = |keywords: &[String]| keywords.iter().any(|k| text.contains(k.as_str()));
+        let has_hashtag =
+            |hashtags_wanted: &HashSet<String>| !hashtags.is_disjoint(hashtags_wanted);
+
+        if has_keyword(&self.exclude_keywords) || has_hashtag(&self.exclude_hashtags) {
+            return false;
+        }
+
+        let restricts_content =
+            !self.include_keywords.is_empty() || !self.include_hashtags.is_empty();
+        if restricts_content
+            && !has_keyword(&self.include_keywords)
+            && !has_hashtag(&self.include_hashtags)
+        {
+            return false;
+        }
+
+        if let Some(query) = &self.query {
+            let context = QueryContext::new(
+                features,
+                &self.language_detector,
+                self.min_language_confidence,
+                self.min_text_length,
+            );
+
+            if !query.matches(&context) {
+                return false;
+            }
+        }
+
+        // Language detection is the most expensive check, so it goes last
+        match &self.language_filter {
+            Some(filter) => filter.matches(&self.language_detector.detect_post(features)),
+            None => true,
+        }
+    }
+}
+
+#[async_trait]
+impl Algo for ConfiguredAlgo {
+    async fn should_index_post(
+        &self,
+        author_did: &str,
+        _post: &<atrium_api::app::bsky::feed::Post as Collection>::Record,
+        features: &PostFeatures,
+    ) -> Result<bool> {
+        Ok(self.accepts(author_did, features))
+    }
+
+    fn filter<'a>(&'a self, viewer_did: Option<&'a str>) -> FeedFilter<'a> {
+        FeedFilter {
+            countries: self.countries.as_deref(),
+            excluded_labels: &self.excluded_labels,
+            viewer_did,
+            list_uri: self.list_uri.as_deref(),
+            excluded_uris: &[],
+        }
+    }
+
+    fn ranking(&self) -> Ranking {
+        self.ranking
+    }
+
+    async fn fetch_posts(
+        &self,
+        database: &Database,
+        feed_name: &str,
+        limit: u8,
+        after: Option<&Cursor>,
+        filter: &FeedFilter<'_>,
+    ) -> Result<Vec<FeedItem>> {
+        let earlier_than = after.map(Cursor::time_and_cid).transpose()?;
+
+        let posts = database
+            .fetch_feed_posts(feed_name, filter, limit as usize, earlier_than)
+            .await?;
+
+        // Lets interactions tell apart how well each ranking does
+        Ok(posts
+            .into_iter()
+            .map(|post| FeedItem {
+                feed_context: Some(self.ranking.name().to_owned()),
+                ..FeedItem::from(post)
+            })
+            .collect())
+    }
+}
+
+async fn resolve_authors(bluesky: &Bluesky, authors: &[String]) -> Result<HashSet<String>> {
+    let mut dids = HashSet::new();
+
+    for author in authors {
+        if author.starts_with("did:") {
+            dids.insert(author.clone());
+        } else {
+            let did = bluesky
+                .resolve_handle(author)
+                .await?
+                .ok_or_else(|| anyhow!("No such user: {author}"))?;
+            dids.insert(did);
+        }
+    }
+
+    Ok(dids)
+}
+
+fn normalize_keywords(keywords: &[String]) -> Vec<String> {
+    keywords
+        .iter()
+        .map(|keyword| normalize_words(keyword))
+        .filter(|keyword| !keyword.is_empty())
+        .map(|keyword| format!(" {keyword} "))
+        .collect()
+}
+
+fn normalize_hashtags(hashtags: &[String]) -> HashSet<String> {
+    hashtags
+        .iter()
+        .map(|hashtag| hashtag.trim_start_matches('#').to_lowercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::config::LabelAction;
+
+    use super::*;
+
+    fn config() -> FeedConfig {
+        FeedConfig {
+            name: "test".to_owned(),
+            ..Default::default()
+        }
+    }
+
+    const PUBLISHER_DID: &str = "did:plc:publisher";
+
+    fn compile(
+        config: &FeedConfig,
+        allowed: &[&str],
+        denied: &[&str],
+        list_members: Arc<ListMembers>,
+    ) -> Result<ConfiguredAlgo> {
+        ConfiguredAlgo::new(
+            config,
+            PUBLISHER_DID,
+            allowed.iter().map(|did| did.to_string()).collect(),
+            denied.iter().map(|did| did.to_string()).collect(),
+            Arc::new(LanguageDetector::new(LanguageDetector::DEFAULT_CANDIDATES)),
+            list_members,
+        )
+    }
+
+    fn algo(config: &FeedConfig, allowed: &[&str], denied: &[&str]) -> ConfiguredAlgo {
+        compile(config, allowed, denied, Arc::new(ListMembers::new()))
+            .expect("config should be valid")
+    }
+
+    fn post(text: &str, tags: &[&str]) -> PostFeatures {
+        PostFeatures {
+            text: text.to_owned(),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn matches_keywords_and_hashtags() {
+        let algo = algo(
+            &FeedConfig {
+                include_keywords: vec!["Rust".to_owned()],
+                include_hashtags: vec!["#RustLang".to_owned()],
+                exclude_keywords: vec!["game".to_owned()],
+                ..config()
+            },
+            &[],
+            &[],
+        );
+
+        assert!(algo.accepts("did:plc:a", &post("I love rust", &[])));
+        assert!(algo.accepts("did:plc:a", &post("Ferris!", &["rustlang"])));
+        assert!(!algo.accepts("did:plc:a", &post("Rust the game", &[])));
+        assert!(!algo.accepts("did:plc:a", &post("I love go", &[])));
+
+        // Only whole words match
+        assert!(!algo.accepts("did:plc:a", &post("In Go we trust", &[])));
+        assert!(algo.accepts("did:plc:a", &post("Games in Rust.", &[])));
+    }
+
+    #[test]
+    fn matches_hashtags_written_without_facets() {
+        let algo = algo(
+            &FeedConfig {
+                include_hashtags: vec!["rustlang".to_owned()],
+                exclude_hashtags: vec!["#Spoilers".to_owned()],
+                ..config()
+            },
+            &[],
+            &[],
+        );
+
+        assert!(algo.accepts("did:plc:a", &post("Ferris! #RustLang", &[])));
+        assert!(!algo.accepts("did:plc:a", &post("#rustlang #spoilers", &[])));
+        assert!(!algo.accepts("did:plc:a", &post("Ferris!", &[])));
+    }
+
+    #[test]
+    fn filters_authors_and_replies() {
+        let config = FeedConfig {
+            include_replies: false,
+            ..config()
+        };
+
+        let algo = algo(&config, &["did:plc:a", "did:plc:b"], &["did:plc:b"]);
+
+        assert!(algo.accepts("did:plc:a", &post("hi", &[])));
+        assert!(!algo.accepts("did:plc:b", &post("hi", &[])));
+        assert!(!algo.accepts("did:plc:c", &post("hi", &[])));
+
+        let reply = PostFeatures {
+            reply: Some(crate::services::bluesky::ReplyTo {
+                root_uri: "at://did:plc:c/app.bsky.feed.post/1".to_owned(),
+                parent_uri: "at://did:plc:c/app.bsky.feed.post/1".to_owned(),
+            }),
+            ..post("hi", &[])
+        };
+        assert!(!algo.accepts("did:plc:a", &reply));
+    }
+
+    #[test]
+    fn filters_self_labels() {
+        let config = FeedConfig {
+            labels: HashMap::from([
+                ("porn".to_owned(), LabelAction::Drop),
+                ("graphic-media".to_owned(), LabelAction::Warn),
+            ]),
+            ..config()
+        };
+
+        let algo = algo(&config, &[], &[]);
+
+        let labeled = |labels: &[&str]| PostFeatures {
+            self_labels: labels.iter().map(|label| label.to_string()).collect(),
+            ..post("hi", &[])
+        };
+
+        assert!(algo.accepts("did:plc:a", &labeled(&[])));
+        assert!(algo.accepts("did:plc:a", &labeled(&["graphic-media"])));
+        assert!(!algo.accepts("did:plc:a", &labeled(&["porn"])));
+        assert_eq!(algo.excluded_labels, vec!["graphic-media", "porn"]);
+    }
+
+    #[test]
+    fn matches_queries() {
+        let algo = algo(
+            &FeedConfig {
+                query: Some("#rustlang OR (rust -game)".to_owned()),
+                ..config()
+            },
+            &[],
+            &[],
+        );
+
+        assert!(algo.accepts("did:plc:a", &post("Ferris!", &["rustlang"])));
+        assert!(algo.accepts("did:plc:a", &post("rust 1.82 is out", &[])));
+        assert!(!algo.accepts("did:plc:a", &post("rust the game", &[])));
+    }
+
+    #[test]
+    fn rejects_invalid_queries() {
+        let result = compile(
+            &FeedConfig {
+                query: Some("rust AND (game".to_owned()),
+                ..config()
+            },
+            &[],
+            &[],
+            Arc::new(ListMembers::new()),
+        );
+
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "Feed test: invalid query: Unclosed parenthesis at position 9"
+        );
+    }
+
+    #[test]
+    fn rejects_languages_that_are_not_detected() {
+        let result = compile(
+            &FeedConfig {
+                languages: vec!["ja".to_owned()],
+                ..config()
+            },
+            &[],
+            &[],
+            Arc::new(ListMembers::new()),
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn follows_list_membership() {
+        let list_members = Arc::new(ListMembers::new());
+        let list_uri = "at://did:plc:publisher/app.bsky.graph.list/curated";
+
+        let algo = compile(
+            &FeedConfig {
+                list: Some("curated".to_owned()),
+                ..config()
+            },
+            &[],
+            &[],
+            list_members.clone(),
+        )
+        .unwrap();
+
+        assert!(!algo.accepts("did:plc:a", &post("hi", &[])));
+
+        list_members.add(list_uri, "did:plc:a");
+        assert!(algo.accepts("did:plc:a", &post("hi", &[])));
+
+        list_members.remove(list_uri, "did:plc:a");
+        assert!(!algo.accepts("did:plc:a", &post("hi", &[])));
+    }
+
+    #[test]
+    fn rejects_lists_of_others() {
+        let result = compile(
+            &FeedConfig {
+                list: Some("at://did:plc:someone/app.bsky.graph.list/curated".to_owned()),
+                ..config()
+            },
+            &[],
+            &[],
+            Arc::new(ListMembers::new()),
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn compiles_rankings() {
+        let hot = |hot_gravity_hours| {
+            compile(
+                &FeedConfig {
+                    ranking: FeedRanking::Hot,
+                    hot_gravity_hours,
+                    ..config()
+                },
+                &[],
+                &[],
+                Arc::new(ListMembers::new()),
+            )
+            .map(|algo| algo.ranking)
+        };
+
+        assert_eq!(algo(&config(), &[], &[]).ranking, Ranking::Chronological);
+        assert_eq!(
+            hot(1.5).unwrap(),
+            Ranking::Hot {
+                gravity: Duration::from_secs(5400)
+            }
+        );
+        assert!(hot(0.0).is_err());
+        assert!(hot(f64::NAN).is_err());
+    }
+}
@@ -0,0 +1,56 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+
+/// Position in a feed that the next page starts after.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cursor {
+    pub key: SortKey,
+    /// CID of the last post served, telling apart posts with the same key
+    pub cid: String,
+}
+
+/// Whatever an algo orders its posts by, from the largest to the smallest.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortKey {
+    Time(DateTime<Utc>),
+    Score(f64),
+}
+
+impl Cursor {
+    pub fn time(time: DateTime<Utc>, cid: &str) -> Self {
+        Self {
+            key: SortKey::Time(time),
+            cid: cid.to_owned(),
+        }
+    }
+
+    pub fn score(score: f64, cid: &str) -> Self {
+        Self {
+            key: SortKey::Score(score),
+            cid: cid.to_owned(),
+        }
+    }
+
+    /// The position in a feed ordered by time.
+    pub fn time_and_cid(&self) -> Result<(DateTime<Utc>, &str), BadCursor> {
+        match self.key {
+            SortKey::Time(time) => Ok((time, &self.cid)),
+            SortKey::Score(_) => Err(BadCursor(
+                "Cursor is not for a feed ordered by time".to_owned(),
+            )),
+        }
+    }
+}
+
+/// A cursor that the client made up, or that was made for another feed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BadCursor(pub String);
+
+impl fmt::Display for BadCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for BadCursor {}
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+
+use crate::config::LabelAction;
+
+/// What a feed does with posts depending on their labels.
+///
+/// Labels that authors put on their own posts are known as soon as the posts are
+/// indexed, and Bluesky always warns viewers about them. Labels from a labeler only
+/// show up later, and viewers that don't subscribe to that labeler never see them,
+/// so posts that would need a warning about those are left out altogether.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LabelPolicy {
+    actions: HashMap<String, LabelAction>,
+}
+
+impl LabelPolicy {
+    pub fn new(actions: HashMap<String, LabelAction>) -> Self {
+        Self { actions }
+    }
+
+    /// Keeps feeds safe for work: no adult content, and graphic media only with a warning.
+    pub fn safe_for_work() -> Self {
+        Self::new(HashMap::from([
+            ("porn".to_owned(), LabelAction::Drop),
+            ("sexual".to_owned(), LabelAction::Drop),
+            ("nudity".to_owned(), LabelAction::Drop),
+            ("graphic-media".to_owned(), LabelAction::Warn),
+        ]))
+    }
+
+    /// Whether a post with the given self-labels can be in the feed.
+    pub fn accepts_self_labels(&self, labels: &[String]) -> bool {
+        !labels
+            .iter()
+            .any(|label| self.actions.get(label) == Some(&LabelAction::Drop))
+    }
+
+    /// Labels from a labeler that keep posts out of the feed.
+    pub fn excluded_labels(&self) -> Vec<String> {
+        let mut labels: Vec<_> = self
+            .actions
+            .iter()
+            .filter(|(_, action)| **action != LabelAction::Allow)
+            .map(|(label, _)| label.clone())
+            .collect();
+
+        labels.sort();
+        labels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(labels: &[&str]) -> Vec<String> {
+        labels.iter().map(|label| label.to_string()).collect()
+    }
+
+    #[test]
+    fn applies_to_self_labels() {
+        let policy = LabelPolicy::safe_for_work();
+
+        assert!(policy.accepts_self_labels(&[]));
+        assert!(policy.accepts_self_labels(&labels(&["graphic-media"])));
+        assert!(policy.accepts_self_labels(&labels(&["something-else"])));
+        assert!(!policy.accepts_self_labels(&labels(&["graphic-media", "porn"])));
+
+        assert!(LabelPolicy::default().accepts_self_labels(&labels(&["porn"])));
+    }
+
+    #[test]
+    fn excludes_labels_that_need_a_warning() {
+        assert_eq!(
+            LabelPolicy::safe_for_work().excluded_labels(),
+            labels(&["graphic-media", "nudity", "porn", "sexual"])
+        );
+
+        let policy = LabelPolicy::new(HashMap::from([
+            ("porn".to_owned(), LabelAction::Allow),
+            ("spam".to_owned(), LabelAction::Drop),
+        ]));
+        assert_eq!(policy.excluded_labels(), labels(&["spam"]));
+    }
+}
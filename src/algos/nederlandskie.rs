@@ -3,21 +3,53 @@ use std::sync::Arc;
 use anyhow::Result;
 use async_trait::async_trait;
 use atrium_api::types::Collection;
-use chrono::{DateTime, Utc};
+use lingua::Language;
 
-use super::Algo;
+use super::{Algo, Cursor, FeedItem, FeedMetadata, LabelPolicy};
 
-use crate::services::database::{self, Database};
+use crate::services::bluesky::{ContentMode, PostFeatures};
+use crate::services::database::{Database, FeedFilter};
+use crate::services::language::LanguageFilter;
+use crate::services::LanguageDetector;
 
 /// An algorithm that serves posts written in Russian by people living in Netherlands
 pub struct Nederlandskie {
     _database: Arc<Database>,
+    language_detector: Arc<LanguageDetector>,
+    language_filter: LanguageFilter,
+    label_policy: LabelPolicy,
+    excluded_labels: Vec<String>,
+    countries: Vec<String>,
 }
 
 impl Nederlandskie {
-    pub fn new(database: Arc<Database>) -> Self {
+    pub const NAME: &'static str = "nederlandskie";
+
+    pub fn metadata() -> FeedMetadata {
+        FeedMetadata {
+            display_name: "Nederlandskie".to_owned(),
+            description: Some("Посты на русском от людей, живущих в Нидерландах".to_owned()),
+            avatar: None,
+            content_mode: ContentMode::Unspecified,
+            accepts_interactions: false,
+        }
+    }
+
+    pub fn new(database: Arc<Database>, language_detector: Arc<LanguageDetector>) -> Self {
+        let label_policy = LabelPolicy::safe_for_work();
+
         Self {
             _database: database,
+            language_detector,
+            // Short posts are too easy to mistake for Ukrainian or Belarusian
+            language_filter: LanguageFilter {
+                languages: vec![Language::Russian],
+                min_confidence: 0.7,
+                min_text_length: 16,
+            },
+            excluded_labels: label_policy.excluded_labels(),
+            label_policy,
+            countries: vec!["nl".to_owned()],
         }
     }
 }
@@ -30,16 +62,38 @@ impl Algo for Nederlandskie {
         &self,
         _author_did: &str,
         _post: &<atrium_api::app::bsky::feed::Post as Collection>::Record,
+        features: &PostFeatures,
     ) -> Result<bool> {
-        todo!()
+        Ok(self.label_policy.accepts_self_labels(&features.self_labels)
+            && self
+                .language_filter
+                .matches(&self.language_detector.detect_post(features)))
+    }
+
+    fn filter<'a>(&'a self, viewer_did: Option<&'a str>) -> FeedFilter<'a> {
+        FeedFilter {
+            countries: Some(&self.countries),
+            excluded_labels: &self.excluded_labels,
+            viewer_did,
+            list_uri: None,
+            excluded_uris: &[],
+        }
     }
 
     async fn fetch_posts(
         &self,
-        _database: &Database,
-        _limit: u8,
-        _earlier_than: Option<(DateTime<Utc>, &str)>,
-    ) -> Result<Vec<database::Post>> {
-        todo!()
+        database: &Database,
+        feed_name: &str,
+        limit: u8,
+        after: Option<&Cursor>,
+        filter: &FeedFilter<'_>,
+    ) -> Result<Vec<FeedItem>> {
+        let earlier_than = after.map(Cursor::time_and_cid).transpose()?;
+
+        let posts = database
+            .fetch_feed_posts(feed_name, filter, limit as usize, earlier_than)
+            .await?;
+
+        Ok(posts.into_iter().map(FeedItem::from).collect())
     }
 }
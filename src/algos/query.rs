@@ -0,0 +1,430 @@
+//! A small query language for matching posts, e.g. `#rustlang OR (rust AND NOT game) lang:en`.
+//!
+//! - Plain words and `"quoted phrases"` match whole words of the post text, case-insensitively
+//! - `#tag` matches hashtags, both the ones in the text and the ones attached to the post
+//! - `lang:xx` matches posts detected to be written in the language with the ISO 639-1 code
+//! - `NOT x` (or `-x`), `x AND y` (or just `x y`) and `x OR y` combine them, in order of
+//!   precedence from highest to lowest, and parentheses group them
+
+use std::cell::OnceCell;
+use std::collections::HashSet;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use lingua::Language;
+
+use crate::services::bluesky::PostFeatures;
+use crate::services::language::{parse_language_tag, LanguageFilter, LanguageScores};
+use crate::services::LanguageDetector;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    Or(Vec<Query>),
+    And(Vec<Query>),
+    Not(Box<Query>),
+    /// One or more lowercase words that must appear in the text next to each other,
+    /// surrounded by single spaces so that only whole words match
+    Words(String),
+    Hashtag(String),
+    Language(Language),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// Byte offset into the query where the error was found
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl Query {
+    pub fn parse(query: &str) -> Result<Self, ParseError> {
+        let tokens = tokenize(query)?;
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+            end: query.len(),
+        };
+
+        let parsed = parser.parse_or()?;
+
+        match parser.peek() {
+            None => Ok(parsed),
+            Some((position, Token::RightParen)) => Err(ParseError {
+                position,
+                message: "Unmatched closing parenthesis".to_owned(),
+            }),
+            Some((position, _)) => Err(ParseError {
+                position,
+                message: "Unexpected token".to_owned(),
+            }),
+        }
+    }
+
+    /// Every language that the query refers to.
+    pub fn languages(&self) -> Vec<Language> {
+        match self {
+            Query::Or(queries) | Query::And(queries) => {
+                queries.iter().flat_map(Query::languages).collect()
+            }
+            Query::Not(query) => query.languages(),
+            Query::Language(language) => vec![*language],
+            Query::Words(_) | Query::Hashtag(_) => Vec::new(),
+        }
+    }
+
+    pub fn matches(&self, post: &QueryContext) -> bool {
+        match self {
+            Query::Or(queries) => queries.iter().any(|query| query.matches(post)),
+            Query::And(queries) => queries.iter().all(|query| query.matches(post)),
+            Query::Not(query) => !query.matches(post),
+            Query::Words(words) => post.text.contains(words.as_str()),
+            Query::Hashtag(hashtag) => post.hashtags.contains(hashtag),
+            Query::Language(language) => post.is_in_language(*language),
+        }
+    }
+}
+
+/// A post prepared for evaluating queries against it.
+///
+/// Language detection is only run if the query gets to a `lang:` term.
+pub struct QueryContext<'a> {
+    /// Lowercase words of the text, separated and surrounded by single spaces
+    text: String,
+    hashtags: HashSet<String>,
+    features: &'a PostFeatures,
+    language_detector: &'a LanguageDetector,
+    min_language_confidence: f64,
+    min_text_length: usize,
+    scores: OnceCell<LanguageScores>,
+}
+
+impl<'a> QueryContext<'a> {
+    pub fn new(
+        features: &'a PostFeatures,
+        language_detector: &'a LanguageDetector,
+        min_language_confidence: f64,
+        min_text_length: usize,
+    ) -> Self {
+        Self {
+            text: format!(" {} ", normalize_words(&features.all_text())),
+            hashtags: post_hashtags(features),
+            features,
+            language_detector,
+            min_language_confidence,
+            min_text_length,
+            scores: OnceCell::new(),
+        }
+    }
+
+    fn is_in_language(&self, language: Language) -> bool {
+        let scores = self
+            .scores
+            .get_or_init(|| self.language_detector.detect_post(self.features));
+
+        LanguageFilter {
+            languages: vec![language],
+            min_confidence: self.min_language_confidence,
+            min_text_length: self.min_text_length,
+        }
+        .matches(scores)
+    }
+}
+
+/// Lowercase words of the text separated by single spaces, leaving out punctuation.
+pub(super) fn normalize_words(text: &str) -> String {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Lowercase hashtags of a post, both the ones attached to it and the ones written
+/// in the text, which not every client turns into facets.
+pub(super) fn post_hashtags(features: &PostFeatures) -> HashSet<String> {
+    features
+        .tags
+        .iter()
+        .map(|tag| tag.to_lowercase())
+        .chain(hashtags_in_text(&features.text))
+        .collect()
+}
+
+fn hashtags_in_text(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split_whitespace()
+        .filter_map(|word| word.strip_prefix('#'))
+        .map(|tag| {
+            tag.chars()
+                .take_while(|c| c.is_alphanumeric() || *c == '_')
+                .collect::<String>()
+                .to_lowercase()
+        })
+        .filter(|tag| !tag.is_empty() && !tag.chars().all(|c| c.is_ascii_digit()))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LeftParen,
+    RightParen,
+    And,
+    Or,
+    Not,
+    Words(String),
+    Hashtag(String),
+    Language(Language),
+}
+
+fn tokenize(query: &str) -> Result<Vec<(usize, Token)>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = query.char_indices().peekable();
+
+    while let Some(&(position, c)) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push((position, Token::LeftParen));
+            }
+            ')' => {
+                chars.next();
+                tokens.push((position, Token::RightParen));
+            }
+            '-' => {
+                chars.next();
+                tokens.push((position, Token::Not));
+            }
+            '"' => {
+                chars.next();
+                let mut phrase = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, c)) => phrase.push(c),
+                        None => {
+                            return Err(ParseError {
+                                position,
+                                message: "Unterminated quote".to_owned(),
+                            })
+                        }
+                    }
+                }
+                tokens.push((position, words_token(position, &phrase)?));
+            }
+            _ => {
+                let word = take_word(&mut chars);
+                let token = match word.as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => {
+                        if let Some(hashtag) = word.strip_prefix('#') {
+                            if hashtag.is_empty() {
+                                return Err(ParseError {
+                                    position,
+                                    message: "Empty hashtag".to_owned(),
+                                });
+                            }
+                            Token::Hashtag(hashtag.to_lowercase())
+                        } else if let Some(tag) = word.strip_prefix("lang:") {
+                            let language = parse_language_tag(tag).ok_or_else(|| ParseError {
+                                position,
+                                message: format!("Unknown language {tag:?}"),
+                            })?;
+                            Token::Language(language)
+                        } else {
+                            words_token(position, &word)?
+                        }
+                    }
+                };
+                tokens.push((position, token));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn take_word(chars: &mut Peekable<CharIndices>) -> String {
+    let mut word = String::new();
+    while let Some(&(_, c)) = chars.peek() {
+        if c.is_whitespace() || c == '(' || c == ')' || c == '"' {
+            break;
+        }
+        word.push(c);
+        chars.next();
+    }
+    word
+}
+
+fn words_token(position: usize, text: &str) -> Result<Token, ParseError> {
+    let words = normalize_words(text);
+    if words.is_empty() {
+        return Err(ParseError {
+            position,
+            message: "Nothing to search for".to_owned(),
+        });
+    }
+    Ok(Token::Words(format!(" {words} ")))
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    position: usize,
+    /// Length of the query, reported when it ends too early
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<(usize, Token)> {
+        self.tokens.get(self.position).cloned()
+    }
+
+    fn next(&mut self) -> Option<(usize, Token)> {
+        let token = self.peek();
+        self.position += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<Query, ParseError> {
+        let mut queries = vec![self.parse_and()?];
+
+        while let Some((_, Token::Or)) = self.peek() {
+            self.next();
+            queries.push(self.parse_and()?);
+        }
+
+        Ok(if queries.len() == 1 {
+            queries.remove(0)
+        } else {
+            Query::Or(queries)
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<Query, ParseError> {
+        let mut queries = vec![self.parse_unary()?];
+
+        loop {
+            match self.peek() {
+                Some((_, Token::And)) => {
+                    self.next();
+                }
+                // Terms next to each other are implicitly combined with AND
+                Some((_, Token::Or | Token::RightParen)) | None => break,
+                Some(_) => {}
+            }
+            queries.push(self.parse_unary()?);
+        }
+
+        Ok(if queries.len() == 1 {
+            queries.remove(0)
+        } else {
+            Query::And(queries)
+        })
+    }
+
+    fn parse_unary(&mut self) -> Result<Query, ParseError> {
+        match self.next() {
+            Some((_, Token::Not)) => Ok(Query::Not(Box::new(self.parse_unary()?))),
+            Some((position, Token::LeftParen)) => {
+                let query = self.parse_or()?;
+                match self.next() {
+                    Some((_, Token::RightParen)) => Ok(query),
+                    _ => Err(ParseError {
+                        position,
+                        message: "Unclosed parenthesis".to_owned(),
+                    }),
+                }
+            }
+            Some((_, Token::Words(words))) => Ok(Query::Words(words)),
+            Some((_, Token::Hashtag(hashtag))) => Ok(Query::Hashtag(hashtag)),
+            Some((_, Token::Language(language))) => Ok(Query::Language(language)),
+            Some((position, token)) => Err(ParseError {
+                position,
+                message: format!("Expected a term, found {token:?}"),
+            }),
+            None => Err(ParseError {
+                position: self.end,
+                message: "Unexpected end of query".to_owned(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(words: &str) -> Query {
+        Query::Words(format!(" {words} "))
+    }
+
+    fn error_position(query: &str) -> usize {
+        Query::parse(query)
+            .expect_err("query should be invalid")
+            .position
+    }
+
+    #[test]
+    fn parses_with_precedence() {
+        assert_eq!(
+            Query::parse("#RustLang OR (rust AND NOT game) lang:en").unwrap(),
+            Query::Or(vec![
+                Query::Hashtag("rustlang".to_owned()),
+                Query::And(vec![
+                    Query::And(vec![words("rust"), Query::Not(Box::new(words("game")))]),
+                    Query::Language(Language::English),
+                ]),
+            ])
+        );
+
+        assert_eq!(
+            Query::parse("\"Rust Belt\" -car").unwrap(),
+            Query::And(vec![words("rust belt"), Query::Not(Box::new(words("car")))])
+        );
+    }
+
+    #[test]
+    fn reports_error_positions() {
+        assert_eq!(error_position("rust AND"), 8);
+        assert_eq!(error_position("rust OR (game"), 8);
+        assert_eq!(error_position("rust) game"), 4);
+        assert_eq!(error_position("rust \"belt"), 5);
+        assert_eq!(error_position("rust lang:klingon"), 5);
+        assert_eq!(error_position("# rust"), 0);
+        assert_eq!(error_position(""), 0);
+    }
+
+    #[test]
+    fn matches_words_hashtags_and_languages() {
+        let detector = LanguageDetector::new(LanguageDetector::DEFAULT_CANDIDATES);
+        let query = Query::parse("#rustlang OR (rust AND NOT game) lang:en").unwrap();
+
+        let matches = |text: &str, tags: &[&str]| {
+            let features = PostFeatures {
+                text: text.to_owned(),
+                tags: tags.iter().map(|tag| tag.to_string()).collect(),
+                ..Default::default()
+            };
+            query.matches(&QueryContext::new(&features, &detector, 0.5, 0))
+        };
+
+        assert!(matches("Гляньте", &["RustLang"]));
+        assert!(matches("Гляньте #RustLang!", &[]));
+        assert!(!matches("Гляньте #rustlanguage #2024", &[]));
+        assert!(matches("Just shipped a new release written in Rust!", &[]));
+        assert!(!matches("Rust is my favourite survival game", &[]));
+        assert!(!matches("Trusting the process", &[]));
+        assert!(!matches("Ik schrijf alles in Rust tegenwoordig", &[]));
+    }
+}
@@ -2,58 +2,62 @@ use anyhow::Result;
 use clap::Parser;
 
 use atrium_api::{app::bsky::feed::Post, types::Collection};
-use env_logger::Env;
-use log::{error, info};
-use nederlandskie::services::bluesky::{self, Operation};
+use lingua::Language;
+use nederlandskie::logging;
+use nederlandskie::services::bluesky::{self, Operation, PostFeatures};
+use nederlandskie::services::LanguageDetector;
 use tokio_stream::StreamExt;
 use tokio_tungstenite::tungstenite;
+use tracing::{error, info};
 
 #[derive(Parser, Debug)]
 struct Args {}
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
+    logging::init(&Default::default());
 
     let _args = Args::parse();
 
+    let language_detector = LanguageDetector::new(LanguageDetector::DEFAULT_CANDIDATES);
+
     let mut stream = bluesky::subscribe_to_operations(None).await?;
 
     while let Some(tungstenite::Message::Binary(message)) = stream.try_next().await? {
         match bluesky::handle_message(&message).await {
             Ok(Some(commit)) => {
                 for operation in commit.operations {
-                    match operation {
-                        Operation::Create {
-                            collection,
-                            did,
-                            cid,
-                            block,
-                        } => {
-                            let uri = format!("at://{}/{}/{}", did.as_str(), collection, cid);
+                    if let Operation::Create {
+                        collection,
+                        did,
+                        rkey,
+                        block,
+                        ..
+                    } = operation
+                    {
+                        let uri = format!("at://{}/{}/{}", did.as_str(), collection, rkey);
 
-                            if collection == atrium_api::app::bsky::feed::Post::NSID {
-                                let post = match serde_ipld_dagcbor::from_slice::<
-                                    <Post as Collection>::Record,
-                                >(&block[..])
-                                {
-                                    Ok(post) => post,
-                                    Err(e) => {
-                                        error!("Error deserializing a post: {:?}", e);
-                                        continue;
-                                    }
-                                };
+                        if collection == atrium_api::app::bsky::feed::Post::NSID {
+                            let post = match serde_ipld_dagcbor::from_slice::<
+                                <Post as Collection>::Record,
+                            >(&block[..])
+                            {
+                                Ok(post) => post,
+                                Err(e) => {
+                                    error!("Error deserializing a post: {:?}", e);
+                                    continue;
+                                }
+                            };
 
-                                if let Some(langs) = &post.langs {
-                                    if langs.iter().any(|lang| {
-                                        lang.as_ref().language().unwrap().primary() == "en"
-                                    }) {
-                                        info!("{uri}: {}", post.text)
-                                    }
+                            if let Some(detected) = language_detector
+                                .detect_post(&PostFeatures::from(&*post))
+                                .most_likely()
+                            {
+                                if detected.language == Language::English {
+                                    info!("{uri} ({:.2}): {}", detected.confidence, post.text)
                                 }
                             }
                         }
-                        _ => {}
                     }
                 }
             }
@@ -0,0 +1,111 @@
+extern crate nederlandskie;
+
+use std::env;
+
+use anyhow::{anyhow, Context, Result};
+use clap::{Parser, Subcommand};
+use dotenv::dotenv;
+
+use nederlandskie::services::{Bluesky, Database};
+
+#[derive(Parser, Debug)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Force a profile to be considered living in the given country
+    Set {
+        /// Handle or DID of the profile
+        #[arg(long)]
+        handle: String,
+
+        /// Two-letter country code, e.g. "nl"
+        #[arg(long)]
+        country: String,
+
+        /// Who is setting the override. Defaults to the current user.
+        #[arg(long)]
+        by: Option<String>,
+    },
+
+    /// Remove the override, going back to the inferred country
+    Clear {
+        /// Handle or DID of the profile
+        #[arg(long)]
+        handle: String,
+    },
+
+    /// List all overrides
+    List,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenv()?;
+
+    let args = Args::parse();
+
+    let database_url =
+        env::var("DATABASE_URL").context("DATABASE_URL environment variable must be set")?;
+
+    let bluesky = Bluesky::unauthenticated();
+    let database = Database::connect(&database_url).await?;
+
+    match args.command {
+        Command::Set {
+            handle,
+            country,
+            by,
+        } => {
+            let did = resolve_did(&bluesky, &handle).await?;
+            let country = country.to_lowercase();
+
+            if country.len() != 2 || !country.chars().all(|c| c.is_ascii_alphabetic()) {
+                return Err(anyhow!("Country must be a two-letter code, got {country}"));
+            }
+
+            let by = by
+                .or_else(|| env::var("USER").ok())
+                .unwrap_or_else(|| "unknown".to_owned());
+
+            database
+                .set_profile_country_override(&did, &country, &by)
+                .await?;
+
+            println!("Stored {country} as the country of {did}");
+        }
+        Command::Clear { handle } => {
+            let did = resolve_did(&bluesky, &handle).await?;
+
+            if database.clear_profile_country_override(&did).await? {
+                println!("Cleared country override of {did}");
+            } else {
+                println!("{did} had no country override");
+            }
+        }
+        Command::List => {
+            for entry in database.fetch_profile_country_overrides().await? {
+                println!(
+                    "{}\t{}\tset by {} at {}",
+                    entry.did, entry.country, entry.set_by, entry.set_at
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn resolve_did(bluesky: &Bluesky, handle: &str) -> Result<String> {
+    if handle.starts_with("did:") {
+        return Ok(handle.to_owned());
+    }
+
+    bluesky
+        .resolve_handle(handle)
+        .await?
+        .ok_or_else(|| anyhow!("No such user: {handle}"))
+}
@@ -0,0 +1,192 @@
+extern crate nederlandskie;
+
+use std::env;
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Parser, Subcommand};
+use dotenv::dotenv;
+
+use nederlandskie::services::{Bluesky, Database};
+
+/// Keeps authors and posts out of every feed
+#[derive(Parser, Debug)]
+struct Args {
+    /// Who is taking the action. Defaults to the current user.
+    #[arg(long, global = true)]
+    by: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Ban an author, hiding their existing posts and not indexing any new ones
+    Ban {
+        /// Handle or DID of the author
+        #[arg(long)]
+        author: String,
+
+        #[arg(long)]
+        reason: String,
+    },
+
+    /// Lift the ban of an author
+    Unban {
+        /// Handle or DID of the author
+        #[arg(long)]
+        author: String,
+    },
+
+    /// Hide a post
+    Hide {
+        /// AT-URI of the post, or its https://bsky.app link
+        #[arg(long)]
+        post: String,
+
+        #[arg(long)]
+        reason: String,
+    },
+
+    /// Show a hidden post again
+    Unhide {
+        /// AT-URI of the post, or its https://bsky.app link
+        #[arg(long)]
+        post: String,
+    },
+
+    /// List banned authors and hidden posts
+    List,
+
+    /// Show the most recent moderation actions
+    Log {
+        #[arg(long, default_value_t = 50)]
+        limit: usize,
+    },
+
+    /// Show the most recent posts that were kept out of the feeds as spam
+    Spam {
+        #[arg(long, default_value_t = 50)]
+        limit: usize,
+    },
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenv()?;
+
+    let args = Args::parse();
+
+    let database_url =
+        env::var("DATABASE_URL").context("DATABASE_URL environment variable must be set")?;
+
+    let bluesky = Bluesky::unauthenticated();
+    let database = Database::connect(&database_url).await?;
+
+    let by = args
+        .by
+        .or_else(|| env::var("USER").ok())
+        .unwrap_or_else(|| "unknown".to_owned());
+
+    match args.command {
+        Command::Ban { author, reason } => {
+            let did = resolve_did(&bluesky, &author).await?;
+
+            database.ban_author(&did, &reason, &by).await?;
+
+            println!("Banned {did}");
+        }
+        Command::Unban { author } => {
+            let did = resolve_did(&bluesky, &author).await?;
+
+            if database.unban_author(&did, &by).await? {
+                println!("Lifted the ban of {did}");
+            } else {
+                println!("{did} wasn't banned");
+            }
+        }
+        Command::Hide { post, reason } => {
+            let uri = resolve_post_uri(&bluesky, &post).await?;
+
+            database.hide_post(&uri, &reason, &by).await?;
+
+            println!("Hid {uri}");
+        }
+        Command::Unhide { post } => {
+            let uri = resolve_post_uri(&bluesky, &post).await?;
+
+            if database.unhide_post(&uri, &by).await? {
+                println!("Unhid {uri}");
+            } else {
+                println!("{uri} wasn't hidden");
+            }
+        }
+        Command::List => {
+            for author in database.fetch_banned_authors().await? {
+                println!(
+                    "banned\t{}\t{}\tby {} at {}",
+                    author.did, author.reason, author.banned_by, author.banned_at
+                );
+            }
+
+            for post in database.fetch_hidden_posts().await? {
+                println!(
+                    "hidden\t{}\t{}\tby {} at {}",
+                    post.uri, post.reason, post.hidden_by, post.hidden_at
+                );
+            }
+        }
+        Command::Log { limit } => {
+            for action in database.fetch_moderation_actions(limit).await? {
+                println!(
+                    "{}\t{}\t{}\t{}\tby {}",
+                    action.performed_at,
+                    action.action,
+                    action.subject,
+                    action.reason.as_deref().unwrap_or("-"),
+                    action.performed_by
+                );
+            }
+        }
+        Command::Spam { limit } => {
+            for decision in database.fetch_spam_decisions(limit).await? {
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    decision.decided_at, decision.reason, decision.uri, decision.details
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn resolve_did(bluesky: &Bluesky, handle: &str) -> Result<String> {
+    if handle.starts_with("did:") {
+        return Ok(handle.to_owned());
+    }
+
+    bluesky
+        .resolve_handle(handle)
+        .await?
+        .ok_or_else(|| anyhow!("No such user: {handle}"))
+}
+
+/// Turns a link to a post in the Bluesky app into its AT-URI.
+async fn resolve_post_uri(bluesky: &Bluesky, post: &str) -> Result<String> {
+    if post.starts_with("at://") {
+        return Ok(post.to_owned());
+    }
+
+    let Some((handle, rkey)) = post
+        .strip_prefix("https://bsky.app/profile/")
+        .and_then(|path| path.split_once("/post/"))
+    else {
+        bail!("Expected an AT-URI or a https://bsky.app link to a post, got {post}");
+    };
+
+    let did = resolve_did(bluesky, handle).await?;
+    let rkey = rkey.trim_end_matches('/');
+
+    Ok(format!("at://{did}/app.bsky.feed.post/{rkey}"))
+}
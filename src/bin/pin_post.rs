@@ -0,0 +1,145 @@
+extern crate nederlandskie;
+
+use std::env;
+
+use anyhow::{anyhow, bail, Result};
+use clap::{Parser, Subcommand};
+use dotenv::dotenv;
+
+use nederlandskie::algos::registered_feeds;
+use nederlandskie::config::Config;
+use nederlandskie::services::{Bluesky, Database};
+
+/// Manages the posts pinned to the top of the feeds
+#[derive(Parser, Debug)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Pin a post to the top of a feed
+    Add {
+        /// Short name of the feed
+        #[arg(long)]
+        feed: String,
+
+        /// AT-URI of the post, or its https://bsky.app link
+        #[arg(long)]
+        post: String,
+
+        /// Who is pinning the post. Defaults to the current user.
+        #[arg(long)]
+        by: Option<String>,
+    },
+
+    /// Unpin a post from a feed
+    Remove {
+        /// Short name of the feed
+        #[arg(long)]
+        feed: String,
+
+        /// AT-URI of the post, or its https://bsky.app link
+        #[arg(long)]
+        post: String,
+    },
+
+    /// List pinned posts
+    List {
+        /// Only list the posts pinned to this feed
+        #[arg(long)]
+        feed: Option<String>,
+    },
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenv()?;
+
+    let args = Args::parse();
+
+    let config = Config::load()?;
+
+    let bluesky = Bluesky::unauthenticated();
+    let database = Database::connect(&config.database_url).await?;
+
+    let feed_names: Vec<_> = registered_feeds(&config)
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+
+    let check_feed = |feed: &str| {
+        if !feed_names.iter().any(|name| name == feed) {
+            bail!("No such feed: {feed}. Registered feeds: {feed_names:?}");
+        }
+
+        Ok(())
+    };
+
+    match args.command {
+        Command::Add { feed, post, by } => {
+            check_feed(&feed)?;
+
+            let uri = resolve_post_uri(&bluesky, &post).await?;
+
+            let by = by
+                .or_else(|| env::var("USER").ok())
+                .unwrap_or_else(|| "unknown".to_owned());
+
+            if database.pin_post(&feed, &uri, &by).await? {
+                println!("Pinned {uri} to {feed}");
+            } else {
+                println!("{uri} is already pinned to {feed}");
+            }
+        }
+        Command::Remove { feed, post } => {
+            check_feed(&feed)?;
+
+            let uri = resolve_post_uri(&bluesky, &post).await?;
+
+            if database.unpin_post(&feed, &uri).await? {
+                println!("Unpinned {uri} from {feed}");
+            } else {
+                println!("{uri} wasn't pinned to {feed}");
+            }
+        }
+        Command::List { feed } => {
+            for pin in database.fetch_pinned_posts(feed.as_deref()).await? {
+                println!(
+                    "{}\t{}\tpinned by {} at {}",
+                    pin.feed, pin.uri, pin.pinned_by, pin.pinned_at
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Turns a link to a post in the Bluesky app into its AT-URI.
+async fn resolve_post_uri(bluesky: &Bluesky, post: &str) -> Result<String> {
+    if post.starts_with("at://") {
+        return Ok(post.to_owned());
+    }
+
+    let Some((handle, rkey)) = post
+        .strip_prefix("https://bsky.app/profile/")
+        .and_then(|path| path.split_once("/post/"))
+    else {
+        bail!("Expected an AT-URI or a https://bsky.app link to a post, got {post}");
+    };
+
+    let did = if handle.starts_with("did:") {
+        handle.to_owned()
+    } else {
+        bluesky
+            .resolve_handle(handle)
+            .await?
+            .ok_or_else(|| anyhow!("No such user: {handle}"))?
+    };
+
+    let rkey = rkey.trim_end_matches('/');
+
+    Ok(format!("at://{did}/app.bsky.feed.post/{rkey}"))
+}
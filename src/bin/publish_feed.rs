@@ -2,29 +2,65 @@ extern crate nederlandskie;
 
 use std::env;
 
-use anyhow::{Context, Result};
-use clap::Parser;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{ArgGroup, Parser, Subcommand};
 use dotenv::dotenv;
 
+use nederlandskie::algos::{registered_feeds, FeedMetadata};
+use nederlandskie::config::Config;
+use nederlandskie::services::bluesky::{blob_cid, FeedGenerator, PublishedFeedGenerator};
 use nederlandskie::services::Bluesky;
 
+/// Manages the feed generator records of the registered feeds, with the display names,
+/// descriptions and avatars declared alongside them
 #[derive(Parser, Debug)]
 struct Args {
-    /// Short name of the feed. Must match one of the defined algos.
-    #[arg(long)]
-    name: String,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// List the feed generator records in our repo
+    List,
+
+    /// Show how the feed generator records in our repo differ from the registered feeds
+    Diff,
 
-    /// Name that will be displayed in Bluesky interface
-    #[arg(long)]
-    display_name: String,
+    /// Publish registered feeds, skipping the ones that are up to date
+    #[command(group(ArgGroup::new("feeds").required(true).args(["name", "all"])))]
+    Publish {
+        /// Short name of the feed. Must match one of the registered feeds.
+        #[arg(long)]
+        name: Option<String>,
 
-    /// Description that will be displayed in Bluesky interface
-    #[arg(long)]
-    description: String,
+        /// Publish every registered feed
+        #[arg(long)]
+        all: bool,
 
-    /// Filename of the avatar that will be displayed
-    #[arg(long)]
-    avatar_filename: Option<String>,
+        /// Publish even the feeds that are up to date
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Delete feed generator records from our repo
+    #[command(group(ArgGroup::new("feeds").required(true).args(["name", "obsolete"])))]
+    Delete {
+        /// Short name of the feed
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Delete the records of every feed that is no longer registered
+        #[arg(long)]
+        obsolete: bool,
+    },
+}
+
+/// A registered feed, along with the contents of its avatar
+struct LocalFeed {
+    name: String,
+    metadata: FeedMetadata,
+    avatar: Option<Vec<u8>>,
 }
 
 #[tokio::main]
@@ -33,13 +69,35 @@ async fn main() -> Result<()> {
 
     let args = Args::parse();
 
+    let config = Config::load()?;
+
     let handle = env::var("PUBLISHER_BLUESKY_HANDLE")
         .context("PUBLISHER_BLUESKY_HANDLE environment variable must be set")?;
 
     let password = env::var("PUBLISHER_BLUESKY_PASSWORD")
         .context("PUBLISHER_BLUESKY_PASSWORD environment variable must be set")?;
 
-    let feed_generator_did = format!("did:web:{}", env::var("FEED_GENERATOR_HOSTNAME")?);
+    let feed_generator_did = format!("did:web:{}", config.feed_generator_hostname);
+
+    let local_feeds = registered_feeds(&config)
+        .into_iter()
+        .map(|(name, metadata)| {
+            let avatar = metadata
+                .avatar
+                .as_ref()
+                .map(|path| {
+                    std::fs::read(path)
+                        .with_context(|| anyhow!("failed to read avatar {}", path.display()))
+                })
+                .transpose()?;
+
+            Ok(LocalFeed {
+                name,
+                metadata,
+                avatar,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
 
     println!("Logging in");
 
@@ -50,23 +108,169 @@ async fn main() -> Result<()> {
         .await?
         .expect("couldn't resolve our own handle, huh?");
 
-    let mut avatar = None;
-    if let Some(path) = args.avatar_filename {
-        let bytes = std::fs::read(path)?;
-        avatar = Some(bluesky.upload_blob(bytes).await?);
-        println!("Uploaded avatar");
+    if publisher_did != config.publisher_did.as_str() {
+        bail!(
+            "Logged in as {publisher_did}, but feeds are served for {}",
+            config.publisher_did.as_str()
+        );
     }
 
-    bluesky
-        .publish_feed(
-            &publisher_did,
-            &feed_generator_did,
-            &args.name,
-            &args.display_name,
-            &args.description,
-            avatar,
-        )
-        .await?;
+    let published = bluesky.fetch_feed_generators(&publisher_did).await?;
+    let find_published = |name: &str| published.iter().find(|feed| feed.name == name);
+
+    match args.command {
+        Command::List => {
+            if published.is_empty() {
+                println!("No feeds are published");
+            }
+
+            for feed in &published {
+                println!(
+                    "{} ({}) served by {}: {}",
+                    feed.name, feed.feed.display_name, feed.did, feed.uri
+                );
+            }
+        }
+        Command::Diff => {
+            for local in &local_feeds {
+                match find_published(&local.name) {
+                    None => println!("{}: not published", local.name),
+                    Some(published) => {
+                        let differences = differences(local, published, &feed_generator_did);
+
+                        if differences.is_empty() {
+                            println!("{}: up to date", local.name);
+                        } else {
+                            println!("{}: different {}", local.name, differences.join(", "));
+                        }
+                    }
+                }
+            }
+
+            for feed in &published {
+                if !local_feeds.iter().any(|local| local.name == feed.name) {
+                    println!("{}: no longer registered", feed.name);
+                }
+            }
+        }
+        Command::Publish { name, force, .. } => {
+            let selected: Vec<_> = match &name {
+                Some(name) => local_feeds
+                    .iter()
+                    .filter(|local| &local.name == name)
+                    .collect(),
+                None => local_feeds.iter().collect(),
+            };
+
+            if let (Some(name), true) = (&name, selected.is_empty()) {
+                bail!("No feed named {name} is registered");
+            }
+
+            for local in selected {
+                let published = find_published(&local.name);
+
+                if let Some(published) = published {
+                    if !force && differences(local, published, &feed_generator_did).is_empty() {
+                        println!("{} is up to date", local.name);
+                        continue;
+                    }
+                }
+
+                // Avatars that didn't change don't need to be uploaded again
+                let avatar = match (&local.avatar, published) {
+                    (Some(bytes), Some(published))
+                        if published.feed.avatar_cid() == Some(blob_cid(bytes)) =>
+                    {
+                        published.feed.avatar.clone()
+                    }
+                    (Some(bytes), _) => {
+                        let avatar = bluesky.upload_blob(bytes.clone()).await?;
+                        println!("Uploaded avatar of {}", local.name);
+                        Some(avatar)
+                    }
+                    (None, _) => None,
+                };
+
+                bluesky
+                    .publish_feed(
+                        &publisher_did,
+                        &feed_generator_did,
+                        &local.name,
+                        &FeedGenerator {
+                            display_name: local.metadata.display_name.clone(),
+                            description: local.metadata.description.clone(),
+                            avatar,
+                            content_mode: local.metadata.content_mode,
+                            accepts_interactions: local.metadata.accepts_interactions,
+                        },
+                        published.map(|published| published.cid.as_str()),
+                    )
+                    .await?;
+
+                match published {
+                    Some(_) => println!("Updated {}", local.name),
+                    None => println!("Published {}", local.name),
+                }
+            }
+        }
+        Command::Delete { name, .. } => {
+            let selected: Vec<_> = match &name {
+                Some(name) => vec![find_published(name)
+                    .ok_or_else(|| anyhow!("No feed named {name} is published"))?],
+                None => published
+                    .iter()
+                    .filter(|feed| !local_feeds.iter().any(|local| local.name == feed.name))
+                    .collect(),
+            };
+
+            if selected.is_empty() {
+                println!("Nothing to delete");
+            }
+
+            for feed in selected {
+                bluesky
+                    .unpublish_feed(&publisher_did, &feed.name, &feed.cid)
+                    .await?;
+
+                println!("Deleted {}", feed.name);
+
+                if local_feeds.iter().any(|local| local.name == feed.name) {
+                    println!(
+                        "{} is still registered, publishing it will bring it back",
+                        feed.name
+                    );
+                }
+            }
+        }
+    }
 
     Ok(())
 }
+
+/// Names of the fields that differ between the registered feed and its published record.
+fn differences(
+    local: &LocalFeed,
+    published: &PublishedFeedGenerator,
+    feed_generator_did: &str,
+) -> Vec<&'static str> {
+    let metadata = &local.metadata;
+    let feed = &published.feed;
+
+    [
+        (published.did != feed_generator_did, "service DID"),
+        (feed.display_name != metadata.display_name, "display name"),
+        (feed.description != metadata.description, "description"),
+        (
+            feed.avatar_cid() != local.avatar.as_deref().map(blob_cid),
+            "avatar",
+        ),
+        (feed.content_mode != metadata.content_mode, "content mode"),
+        (
+            feed.accepts_interactions != metadata.accepts_interactions,
+            "accepting interactions",
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(differs, field)| differs.then_some(field))
+    .collect()
+}
@@ -1,10 +1,234 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
 use atrium_api::types::string::Did;
+use figment::providers::{Env, Format, Toml};
+use figment::Figment;
 use serde::Deserialize;
 
+use crate::services::bluesky::ContentMode;
+
 #[derive(Deserialize, Clone)]
 pub struct Config {
     pub chat_gpt_api_key: String,
     pub database_url: String,
     pub publisher_did: Did,
     pub feed_generator_hostname: String,
+    /// Secret that cursors handed out to clients are signed with. If it's not set,
+    /// a random one is used, and cursors stop being accepted after a restart.
+    pub cursor_secret: Option<String>,
+    /// Bearer token that the admin API under `/admin` is guarded by. The admin API
+    /// is only served when it's set.
+    pub admin_token: Option<String>,
+    /// Links shown to people looking into the feed generator
+    pub privacy_policy_url: Option<String>,
+    pub terms_of_service_url: Option<String>,
+    /// Websocket URL of a labeler whose labels feeds apply their label policies to,
+    /// e.g. `wss://mod.bsky.app`
+    pub labeler_url: Option<String>,
+    #[serde(default)]
+    pub spam: SpamConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub feeds: Vec<FeedConfig>,
+}
+
+impl Config {
+    /// Loads the configuration from `config.toml`, overridden by `BSKY_` environment variables.
+    pub fn load() -> Result<Self> {
+        Figment::new()
+            .merge(Toml::file("config.toml"))
+            .merge(Env::prefixed("BSKY_"))
+            .extract()
+            .context("failed to load configuration")
+    }
+}
+
+/// Thresholds for keeping posts of likely spammers and bots out of every feed.
+/// Setting any of them to zero turns its check off.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct SpamConfig {
+    /// How old accounts have to be for their posts to be accepted
+    #[serde(default)]
+    pub min_account_age_hours: f64,
+    /// How many posts an author can make within an hour before they're taken for a bot
+    #[serde(default = "default_max_posts_per_hour")]
+    pub max_posts_per_hour: usize,
+    /// How many authors can post the same text within an hour before it's taken for spam
+    #[serde(default = "default_max_duplicate_authors")]
+    pub max_duplicate_authors: usize,
+    /// Whether posts with nothing but links in them are skipped
+    #[serde(default)]
+    pub reject_link_only_posts: bool,
+}
+
+impl Default for SpamConfig {
+    fn default() -> Self {
+        Self {
+            min_account_age_hours: 0.0,
+            max_posts_per_hour: default_max_posts_per_hour(),
+            max_duplicate_authors: default_max_duplicate_authors(),
+            reject_link_only_posts: false,
+        }
+    }
+}
+
+/// How the feed generator logs what it's doing. The level of detail is set with
+/// `RUST_LOG`, `info` by default.
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct LoggingConfig {
+    #[serde(default)]
+    pub format: LogFormat,
+    /// Whether the text of indexed posts is logged. Off by default, since posts can
+    /// say anything about anyone.
+    #[serde(default)]
+    pub include_post_text: bool,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Lines meant for people to read
+    #[default]
+    Text,
+    /// A JSON object per line, with the fields of the spans that events happened in
+    Json,
+}
+
+/// A feed defined entirely in configuration, see [`crate::algos::ConfiguredAlgo`].
+///
+/// Every list that's left empty doesn't restrict anything.
+#[derive(Deserialize, Clone, Debug)]
+pub struct FeedConfig {
+    /// Short name of the feed, used as the record key of the feed generator record
+    pub name: String,
+    /// Name shown in Bluesky, the short name if not set
+    pub display_name: Option<String>,
+    /// Description shown in Bluesky. Links, mentions and hashtags in it are clickable.
+    pub description: Option<String>,
+    /// Path to the image shown as the avatar of the feed
+    pub avatar: Option<PathBuf>,
+    #[serde(default)]
+    pub content_mode: ContentMode,
+    /// Whether clients should send interactions with posts of the feed to us
+    #[serde(default)]
+    pub accepts_interactions: bool,
+    /// ISO 639-1 codes of the languages that posts must be written in
+    #[serde(default)]
+    pub languages: Vec<String>,
+    /// Two-letter codes of the countries that authors must live in
+    #[serde(default)]
+    pub countries: Vec<String>,
+    /// Posts must contain at least one of these keywords or hashtags
+    #[serde(default)]
+    pub include_keywords: Vec<String>,
+    #[serde(default)]
+    pub include_hashtags: Vec<String>,
+    /// Posts containing any of these keywords or hashtags are skipped
+    #[serde(default)]
+    pub exclude_keywords: Vec<String>,
+    #[serde(default)]
+    pub exclude_hashtags: Vec<String>,
+    /// Query that posts must match, see [`crate::algos::query`] for the syntax
+    pub query: Option<String>,
+    /// Record key or AT-URI of a list owned by the publisher. Only posts by
+    /// members of the list are accepted, following changes to the list as they happen.
+    pub list: Option<String>,
+    /// Handles or DIDs of the only authors whose posts are accepted
+    #[serde(default)]
+    pub allowed_authors: Vec<String>,
+    /// Handles or DIDs of authors whose posts are never accepted
+    #[serde(default)]
+    pub denied_authors: Vec<String>,
+    #[serde(default)]
+    pub ranking: FeedRanking,
+    /// How many hours fresher a post ranks every time its likes and reposts double,
+    /// for hot feeds
+    #[serde(default = "default_hot_gravity_hours")]
+    pub hot_gravity_hours: f64,
+    /// What to do with posts carrying each label, either set by the author or by
+    /// the labeler. Posts with labels that aren't mentioned are allowed.
+    #[serde(default)]
+    pub labels: HashMap<String, LabelAction>,
+    #[serde(default = "default_include_replies")]
+    pub include_replies: bool,
+    #[serde(default = "default_min_language_confidence")]
+    pub min_language_confidence: f64,
+    #[serde(default)]
+    pub min_text_length: usize,
+}
+
+/// A feed without a name that accepts every post, with the same defaults as when
+/// it's loaded from configuration.
+impl Default for FeedConfig {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            display_name: None,
+            description: None,
+            avatar: None,
+            content_mode: ContentMode::default(),
+            accepts_interactions: false,
+            languages: Vec::new(),
+            countries: Vec::new(),
+            include_keywords: Vec::new(),
+            include_hashtags: Vec::new(),
+            exclude_keywords: Vec::new(),
+            exclude_hashtags: Vec::new(),
+            query: None,
+            list: None,
+            allowed_authors: Vec::new(),
+            denied_authors: Vec::new(),
+            ranking: FeedRanking::default(),
+            hot_gravity_hours: default_hot_gravity_hours(),
+            labels: HashMap::new(),
+            include_replies: default_include_replies(),
+            min_language_confidence: default_min_language_confidence(),
+            min_text_length: 0,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedRanking {
+    /// Newest posts first
+    #[default]
+    Chronological,
+    /// Posts with more likes and reposts first, with older posts sinking over time
+    Hot,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LabelAction {
+    /// Keep the post
+    Allow,
+    /// Keep the post only if viewers are sure to get a warning about the label,
+    /// which is the case when the author labeled it themselves
+    Warn,
+    /// Skip the post
+    Drop,
+}
+
+fn default_max_posts_per_hour() -> usize {
+    60
+}
+
+fn default_max_duplicate_authors() -> usize {
+    3
+}
+
+fn default_hot_gravity_hours() -> f64 {
+    6.0
+}
+
+fn default_include_replies() -> bool {
+    true
+}
+
+fn default_min_language_confidence() -> f64 {
+    0.5
 }
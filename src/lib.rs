@@ -1,4 +1,5 @@
 pub mod algos;
 pub mod config;
+pub mod logging;
 pub mod processes;
 pub mod services;
@@ -4,33 +4,63 @@ use std::pin::pin;
 use std::sync::Arc;
 
 use anyhow::{bail, Context, Result};
-use figment::providers::{Env, Format, Toml};
-use figment::Figment;
-use log::info;
-use tokio::sync::broadcast;
+use chrono::Utc;
+use tokio::sync::mpsc;
 use tokio_stream::StreamExt;
 use tokio_tungstenite::tungstenite;
+use tracing::{info, warn};
 
-use nederlandskie::algos::{AlgosBuilder, Nederlandskie};
+use nederlandskie::algos::{AlgosBuilder, ConfiguredAlgo, Nederlandskie};
 use nederlandskie::config::Config;
-use nederlandskie::processes::{feed_server, post_indexer};
+use nederlandskie::logging;
+use nederlandskie::processes::{
+    account_checker, feed_server, label_indexer, post_indexer, profile_classifier,
+};
 use nederlandskie::services::bluesky::{CommitDetails, FIREHOSE_HOST};
-use nederlandskie::services::{bluesky, Bluesky, Database};
+use nederlandskie::services::database::ListItem;
+use nederlandskie::services::{
+    bluesky, Bluesky, Database, Health, LanguageDetector, ListMembers, Metrics, SpamFilter, AI,
+};
+
+/// How many firehose commits can wait to be indexed before reading the firehose waits
+/// for indexing to catch up.
+const FIREHOSE_QUEUE_CAPACITY: usize = 1000;
 
 /// This is the primary point where messages are consumed from the BlueSky network.
 ///
-/// Once ingested and validated, the message is then forwarded out to the broadcast
-/// channel via `tx`.
-async fn firehose_server(cursor: Option<i64>, tx: broadcast::Sender<CommitDetails>) -> Result<()> {
+/// Once ingested and validated, the message is then forwarded out to the indexer via
+/// `tx`, waiting for it whenever it falls behind so that nothing gets lost.
+async fn firehose_server(
+    cursor: Option<i64>,
+    tx: mpsc::Sender<CommitDetails>,
+    metrics: Arc<Metrics>,
+    health: Arc<Health>,
+) -> Result<()> {
+    let result = consume_firehose(cursor, &tx, &metrics, &health).await;
+
+    health.set_firehose_connected(false, Utc::now());
+
+    result
+}
+
+async fn consume_firehose(
+    cursor: Option<i64>,
+    tx: &mpsc::Sender<CommitDetails>,
+    metrics: &Metrics,
+    health: &Health,
+) -> Result<()> {
     let mut stream = pin!(bluesky::subscribe_to_operations(cursor)
         .await
         .context("failed to subscribe")?
         .timeout(bluesky::STREAMING_TIMEOUT));
 
+    health.set_firehose_connected(true, Utc::now());
+
     while let Some(Ok(tungstenite::Message::Binary(message))) = stream.try_next().await? {
         match bluesky::handle_message(&message).await {
             Ok(Some(commit)) => {
-                tx.send(commit)?;
+                metrics.observe_firehose_commit(&commit);
+                tx.send(commit).await?;
             }
             Ok(None) => continue,
             Err(e) => bail!("Error handling a message: {:?}", e),
@@ -42,33 +72,83 @@ async fn firehose_server(cursor: Option<i64>, tx: broadcast::Sender<CommitDetail
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    let config = Arc::new(Config::load()?);
 
-    info!("Loading configuration");
-
-    let config = Arc::new(
-        Figment::new()
-            .merge(Toml::file("config.toml"))
-            .merge(Env::prefixed("BSKY_"))
-            .extract::<Config>()
-            .context("failed to load configuration")?,
-    );
+    logging::init(&config.logging);
 
     info!("Initializing service clients");
 
-    let _bluesky = Arc::new(Bluesky::unauthenticated());
+    let bluesky = Arc::new(Bluesky::unauthenticated());
+    let ai = Arc::new(AI::new(&config.chat_gpt_api_key, "https://api.openai.com"));
+    let language_detector = Arc::new(LanguageDetector::new(LanguageDetector::DEFAULT_CANDIDATES));
+    let spam_filter = Arc::new(SpamFilter::new(config.spam.clone()));
+    let metrics = Arc::new(Metrics::new());
+    let health = Arc::new(Health::new());
     let database = Arc::new(
         Database::connect(&config.database_url)
             .await
             .context("failed to connect to database")?,
     );
 
-    let algos = Arc::new(
-        AlgosBuilder::new()
-            .add("nederlandskie", Nederlandskie::new(database.clone()))
-            .build(),
+    info!("Synchronizing curated lists");
+
+    let list_members = Arc::new(ListMembers::new());
+
+    match bluesky
+        .fetch_list_items(config.publisher_did.as_str())
+        .await
+    {
+        Ok(items) => {
+            let items: Vec<_> = items
+                .into_iter()
+                .map(|item| ListItem {
+                    uri: item.uri,
+                    list_uri: item.list,
+                    subject_did: item.subject,
+                })
+                .collect();
+
+            database
+                .replace_list_items(config.publisher_did.as_str(), &items)
+                .await?;
+        }
+        Err(e) => warn!("Failed to fetch list items, using the stored ones: {e:?}"),
+    }
+
+    let list_items = database.fetch_list_items().await?;
+    list_members.replace_all(
+        list_items
+            .iter()
+            .map(|item| (item.list_uri.as_str(), item.subject_did.as_str())),
+    );
+
+    let mut algos = AlgosBuilder::new().add(
+        Nederlandskie::NAME,
+        Nederlandskie::new(database.clone(), language_detector.clone()),
     );
 
+    for feed in &config.feeds {
+        if algos.contains(&feed.name) {
+            bail!("Feed {} is defined more than once", feed.name);
+        }
+
+        let algo = ConfiguredAlgo::compile(
+            feed,
+            config.publisher_did.as_str(),
+            language_detector.clone(),
+            list_members.clone(),
+            &bluesky,
+        )
+        .await
+        .with_context(|| format!("failed to compile feed {}", feed.name))?;
+
+        info!("Compiled configured feed {}", feed.name);
+
+        algos = algos.add(&feed.name, algo);
+    }
+
+    let algos = Arc::new(algos.build());
+
     let cursor = database
         .fetch_subscription_cursor(FIREHOSE_HOST, &config.feed_generator_hostname)
         .await?;
@@ -79,22 +159,42 @@ async fn main() -> Result<()> {
             .await?;
     }
 
-    let (tx, rx) = broadcast::channel(10);
+    let (tx, rx) = mpsc::channel(FIREHOSE_QUEUE_CAPACITY);
+    let (account_checks_tx, account_checks_rx) = mpsc::channel(account_checker::QUEUE_CAPACITY);
 
     info!("Starting everything up");
 
     let _ = tokio::try_join!(
-        tokio::spawn(firehose_server(cursor, tx)),
+        tokio::spawn(firehose_server(cursor, tx, metrics.clone(), health.clone())),
         tokio::spawn(post_indexer::start(
             database.clone(),
             config.clone(),
             algos.clone(),
-            rx
+            list_members.clone(),
+            spam_filter.clone(),
+            metrics.clone(),
+            health.clone(),
+            rx,
+            account_checks_tx
+        )),
+        tokio::spawn(account_checker::start(
+            database.clone(),
+            spam_filter.clone(),
+            account_checks_rx
+        )),
+        tokio::spawn(label_indexer::start(database.clone(), config.clone())),
+        tokio::spawn(profile_classifier::start(
+            database.clone(),
+            ai.clone(),
+            bluesky.clone(),
+            metrics.clone()
         )),
         tokio::spawn(feed_server::serve(
             database.clone(),
             config.clone(),
-            algos.clone()
+            algos.clone(),
+            metrics.clone(),
+            health.clone()
         )),
     )
     .context("failed to join tasks")?;
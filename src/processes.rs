@@ -1,2 +1,5 @@
+pub mod account_checker;
 pub mod feed_server;
+pub mod label_indexer;
 pub mod post_indexer;
+pub mod profile_classifier;
@@ -0,0 +1,81 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::services::spam::AccountAgeCheck;
+use crate::services::{Database, SpamFilter};
+
+/// How many posts can wait for the accounts of their authors to be checked, beyond
+/// which posts are let in unchecked rather than holding up indexing.
+pub const QUEUE_CAPACITY: usize = 1000;
+
+/// Looks up how old accounts are for posts that went into feeds before it was known,
+/// so that indexing is never held up by the PLC directory, and takes posts of accounts
+/// that turn out to be too new back out of the feeds.
+pub async fn start(
+    database: Arc<Database>,
+    spam_filter: Arc<SpamFilter>,
+    mut checks: mpsc::Receiver<AccountAgeCheck>,
+) -> Result<()> {
+    while let Some(check) = checks.recv().await {
+        if let Err(e) = process_check(&database, &spam_filter, &check).await {
+            warn!(
+                "Failed to find out the age of {}, leaving {} in: {e:?}",
+                check.did, check.uri
+            );
+        }
+    }
+
+    Ok(())
+}
+
+async fn process_check(
+    database: &Database,
+    spam_filter: &SpamFilter,
+    check: &AccountAgeCheck,
+) -> Result<()> {
+    // Another post by the same author may have been checked in the meantime
+    let known_created_at = database
+        .fetch_profile_age(&check.did)
+        .await?
+        .and_then(|age| age.account_created_at);
+
+    let created_at = match known_created_at {
+        Some(created_at) => created_at,
+        None => {
+            let Some(created_at) = spam_filter.fetch_account_created_at(&check.did).await? else {
+                return Ok(());
+            };
+
+            database
+                .store_account_created_at(&check.did, created_at)
+                .await?;
+
+            created_at
+        }
+    };
+
+    let Some(verdict) = spam_filter.check_account_age(created_at, check.posted_at) else {
+        return Ok(());
+    };
+
+    info!(
+        "Took a post from {} out of feeds as spam, {}: {}",
+        check.did, verdict.details, check.uri
+    );
+
+    database
+        .record_spam_decision(
+            &check.did,
+            &check.uri,
+            verdict.reason.as_str(),
+            &verdict.details,
+        )
+        .await?;
+
+    database.delete_post(&check.uri).await?;
+
+    Ok(())
+}
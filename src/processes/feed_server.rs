@@ -1,5 +1,9 @@
+mod admin;
+mod auth;
+mod cursor;
 mod endpoints;
 mod errors;
+mod request_span;
 mod server;
 mod state;
 
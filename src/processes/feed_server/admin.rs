@@ -0,0 +1,146 @@
+mod moderation;
+mod pins;
+mod profiles;
+mod stats;
+
+use axum::extract::Request;
+use axum::http::header::AUTHORIZATION;
+use axum::http::HeaderMap;
+use axum::middleware::{self, Next};
+use axum::response::Response;
+use axum::routing::{get, post};
+use axum::Router;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use super::errors::AppError;
+use super::state::FeedServerState;
+
+/// Who actions taken through the admin API are recorded as. Everyone with the token
+/// looks the same, so requests don't get to say who they were made by.
+const ACTOR: &str = "admin-api";
+
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 1000;
+
+/// Routes for managing the feeds, all of them guarded by the given bearer token.
+pub fn router(token: &str) -> Router<FeedServerState> {
+    let token_digest: [u8; 32] = Sha256::digest(token.as_bytes()).into();
+
+    Router::new()
+        .route("/stats", get(stats::stats))
+        .route("/profiles", get(profiles::list_profiles))
+        .route("/reindex", post(profiles::reindex_profiles))
+        .route(
+            "/country-overrides",
+            get(profiles::list_country_overrides)
+                .post(profiles::set_country_override)
+                .delete(profiles::clear_country_override),
+        )
+        .route(
+            "/bans",
+            get(moderation::list_bans)
+                .post(moderation::ban)
+                .delete(moderation::unban),
+        )
+        .route(
+            "/hidden-posts",
+            get(moderation::list_hidden_posts)
+                .post(moderation::hide)
+                .delete(moderation::unhide),
+        )
+        .route("/moderation-log", get(moderation::moderation_log))
+        .route("/spam-decisions", get(moderation::spam_decisions))
+        .route(
+            "/pins",
+            get(pins::list_pins).post(pins::pin).delete(pins::unpin),
+        )
+        .route_layer(middleware::from_fn(
+            move |request: Request, next: Next| async move {
+                if has_token(request.headers(), &token_digest) {
+                    Ok(next.run(request).await)
+                } else {
+                    Err::<Response, _>(AppError::AuthenticationRequired(
+                        "A valid admin token is required".to_owned(),
+                    ))
+                }
+            },
+        ))
+}
+
+/// Whether a request carries the admin token with the given digest.
+///
+/// Digests are compared rather than the tokens themselves, so that how long the
+/// comparison takes doesn't give away how much of a token is right.
+fn has_token(headers: &HeaderMap, token_digest: &[u8; 32]) -> bool {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|header| header.to_str().ok())
+        .and_then(|header| header.strip_prefix("Bearer "))
+        .is_some_and(|token| Sha256::digest(token.trim().as_bytes()).as_slice() == token_digest)
+}
+
+#[derive(Deserialize)]
+struct LimitParams {
+    limit: Option<usize>,
+}
+
+impl LimitParams {
+    fn limit(&self) -> usize {
+        limit(self.limit)
+    }
+}
+
+/// How many items to list, when asked for the given number.
+///
+/// Query parameters can't be flattened into other parameters, since flattened fields
+/// are only ever read as strings, so the ones with more to them call this instead.
+fn limit(limit: Option<usize>) -> usize {
+    limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT)
+}
+
+fn expect_did(did: &str) -> Result<(), AppError> {
+    if !did.starts_with("did:") {
+        return Err(AppError::InvalidRequest(format!(
+            "Expected a DID, got {did}"
+        )));
+    }
+
+    Ok(())
+}
+
+fn expect_post_uri(uri: &str) -> Result<(), AppError> {
+    if !uri.starts_with("at://") || !uri.contains("/app.bsky.feed.post/") {
+        return Err(AppError::InvalidRequest(format!(
+            "Expected an AT-URI of a post, got {uri}"
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    fn headers(authorization: Option<&'static str>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Some(authorization) = authorization {
+            headers.insert(AUTHORIZATION, HeaderValue::from_static(authorization));
+        }
+        headers
+    }
+
+    #[test]
+    fn checks_the_token() {
+        let digest: [u8; 32] = Sha256::digest(b"s3cret").into();
+
+        assert!(has_token(&headers(Some("Bearer s3cret")), &digest));
+        assert!(!has_token(&headers(Some("Bearer s3cre")), &digest));
+        assert!(!has_token(&headers(Some("Bearer ")), &digest));
+        assert!(!has_token(&headers(Some("s3cret")), &digest));
+        assert!(!has_token(&headers(None), &digest));
+    }
+}
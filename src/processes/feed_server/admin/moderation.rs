@@ -0,0 +1,125 @@
+use std::sync::Arc;
+
+use axum::extract::rejection::{JsonRejection, QueryRejection};
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::Json;
+use serde::Deserialize;
+
+use crate::processes::feed_server::errors::AppError;
+use crate::services::database::{BannedAuthor, HiddenPost, ModerationAction, SpamDecision};
+use crate::services::Database;
+
+use super::{expect_did, expect_post_uri, LimitParams, ACTOR};
+
+pub async fn list_bans(
+    State(database): State<Arc<Database>>,
+) -> Result<Json<Vec<BannedAuthor>>, AppError> {
+    Ok(Json(database.fetch_banned_authors().await?))
+}
+
+#[derive(Deserialize)]
+pub struct BanInput {
+    did: String,
+    reason: String,
+}
+
+pub async fn ban(
+    State(database): State<Arc<Database>>,
+    input: Result<Json<BanInput>, JsonRejection>,
+) -> Result<StatusCode, AppError> {
+    let Json(input) = input.map_err(|rejection| AppError::InvalidRequest(rejection.body_text()))?;
+
+    expect_did(&input.did)?;
+
+    database
+        .ban_author(&input.did, &input.reason, ACTOR)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+pub struct UnbanParams {
+    did: String,
+}
+
+pub async fn unban(
+    State(database): State<Arc<Database>>,
+    params: Result<Query<UnbanParams>, QueryRejection>,
+) -> Result<StatusCode, AppError> {
+    let Query(params) =
+        params.map_err(|rejection| AppError::InvalidRequest(rejection.body_text()))?;
+
+    if !database.unban_author(&params.did, ACTOR).await? {
+        return Err(AppError::NotFound(format!("{} isn't banned", params.did)));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_hidden_posts(
+    State(database): State<Arc<Database>>,
+) -> Result<Json<Vec<HiddenPost>>, AppError> {
+    Ok(Json(database.fetch_hidden_posts().await?))
+}
+
+#[derive(Deserialize)]
+pub struct HideInput {
+    uri: String,
+    reason: String,
+}
+
+pub async fn hide(
+    State(database): State<Arc<Database>>,
+    input: Result<Json<HideInput>, JsonRejection>,
+) -> Result<StatusCode, AppError> {
+    let Json(input) = input.map_err(|rejection| AppError::InvalidRequest(rejection.body_text()))?;
+
+    expect_post_uri(&input.uri)?;
+
+    database.hide_post(&input.uri, &input.reason, ACTOR).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+pub struct UnhideParams {
+    uri: String,
+}
+
+pub async fn unhide(
+    State(database): State<Arc<Database>>,
+    params: Result<Query<UnhideParams>, QueryRejection>,
+) -> Result<StatusCode, AppError> {
+    let Query(params) =
+        params.map_err(|rejection| AppError::InvalidRequest(rejection.body_text()))?;
+
+    if !database.unhide_post(&params.uri, ACTOR).await? {
+        return Err(AppError::NotFound(format!("{} isn't hidden", params.uri)));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn moderation_log(
+    State(database): State<Arc<Database>>,
+    params: Result<Query<LimitParams>, QueryRejection>,
+) -> Result<Json<Vec<ModerationAction>>, AppError> {
+    let Query(params) =
+        params.map_err(|rejection| AppError::InvalidRequest(rejection.body_text()))?;
+
+    Ok(Json(
+        database.fetch_moderation_actions(params.limit()).await?,
+    ))
+}
+
+pub async fn spam_decisions(
+    State(database): State<Arc<Database>>,
+    params: Result<Query<LimitParams>, QueryRejection>,
+) -> Result<Json<Vec<SpamDecision>>, AppError> {
+    let Query(params) =
+        params.map_err(|rejection| AppError::InvalidRequest(rejection.body_text()))?;
+
+    Ok(Json(database.fetch_spam_decisions(params.limit()).await?))
+}
@@ -0,0 +1,79 @@
+use std::sync::Arc;
+
+use axum::extract::rejection::{JsonRejection, QueryRejection};
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::Json;
+use serde::Deserialize;
+
+use crate::algos::Algos;
+use crate::processes::feed_server::errors::AppError;
+use crate::services::database::PinnedPost;
+use crate::services::Database;
+
+use super::{expect_post_uri, ACTOR};
+
+#[derive(Deserialize)]
+pub struct ListPinsParams {
+    /// Only list the posts pinned to this feed
+    feed: Option<String>,
+}
+
+pub async fn list_pins(
+    State(database): State<Arc<Database>>,
+    params: Result<Query<ListPinsParams>, QueryRejection>,
+) -> Result<Json<Vec<PinnedPost>>, AppError> {
+    let Query(params) =
+        params.map_err(|rejection| AppError::InvalidRequest(rejection.body_text()))?;
+
+    Ok(Json(
+        database.fetch_pinned_posts(params.feed.as_deref()).await?,
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct PinInput {
+    feed: String,
+    uri: String,
+}
+
+pub async fn pin(
+    State(database): State<Arc<Database>>,
+    State(algos): State<Arc<Algos>>,
+    input: Result<Json<PinInput>, JsonRejection>,
+) -> Result<StatusCode, AppError> {
+    let Json(input) = input.map_err(|rejection| AppError::InvalidRequest(rejection.body_text()))?;
+
+    if algos.get_by_name(&input.feed).is_none() {
+        return Err(AppError::UnknownFeed(input.feed));
+    }
+
+    expect_post_uri(&input.uri)?;
+
+    database.pin_post(&input.feed, &input.uri, ACTOR).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+pub struct UnpinParams {
+    feed: String,
+    uri: String,
+}
+
+pub async fn unpin(
+    State(database): State<Arc<Database>>,
+    params: Result<Query<UnpinParams>, QueryRejection>,
+) -> Result<StatusCode, AppError> {
+    let Query(params) =
+        params.map_err(|rejection| AppError::InvalidRequest(rejection.body_text()))?;
+
+    if !database.unpin_post(&params.feed, &params.uri).await? {
+        return Err(AppError::NotFound(format!(
+            "{} isn't pinned to {}",
+            params.uri, params.feed
+        )));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,147 @@
+use std::sync::Arc;
+
+use axum::extract::rejection::{JsonRejection, QueryRejection};
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+
+use crate::processes::feed_server::errors::AppError;
+use crate::services::database::{ProfileCountry, ProfileCountryOverride};
+use crate::services::Database;
+
+use super::{expect_did, limit, ACTOR};
+
+#[derive(Deserialize)]
+pub struct ListProfilesParams {
+    /// Only list profiles considered to be living in this country
+    country: Option<String>,
+    limit: Option<usize>,
+}
+
+pub async fn list_profiles(
+    State(database): State<Arc<Database>>,
+    params: Result<Query<ListProfilesParams>, QueryRejection>,
+) -> Result<Json<Vec<ProfileCountry>>, AppError> {
+    let Query(params) =
+        params.map_err(|rejection| AppError::InvalidRequest(rejection.body_text()))?;
+
+    let country = params.country.map(|country| country.to_lowercase());
+
+    Ok(Json(
+        database
+            .fetch_profile_countries(country.as_deref(), limit(params.limit))
+            .await?,
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct ReindexInput {
+    /// Only reclassify this profile rather than all of them
+    did: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ReindexOutput {
+    /// How many profiles are going to be classified again
+    profiles: u64,
+}
+
+/// Has the country of living of a profile, or of every profile, inferred again.
+pub async fn reindex_profiles(
+    State(database): State<Arc<Database>>,
+    input: Result<Json<ReindexInput>, JsonRejection>,
+) -> Result<Json<ReindexOutput>, AppError> {
+    let Json(input) = input.map_err(|rejection| AppError::InvalidRequest(rejection.body_text()))?;
+
+    if let Some(did) = &input.did {
+        expect_did(did)?;
+    }
+
+    Ok(Json(ReindexOutput {
+        profiles: database
+            .reset_profile_classification(input.did.as_deref())
+            .await?,
+    }))
+}
+
+pub async fn list_country_overrides(
+    State(database): State<Arc<Database>>,
+) -> Result<Json<Vec<ProfileCountryOverride>>, AppError> {
+    Ok(Json(database.fetch_profile_country_overrides().await?))
+}
+
+#[derive(Deserialize)]
+pub struct SetCountryOverrideInput {
+    did: String,
+    /// Two-letter country code, e.g. "nl"
+    country: String,
+}
+
+pub async fn set_country_override(
+    State(database): State<Arc<Database>>,
+    input: Result<Json<SetCountryOverrideInput>, JsonRejection>,
+) -> Result<StatusCode, AppError> {
+    let Json(input) = input.map_err(|rejection| AppError::InvalidRequest(rejection.body_text()))?;
+
+    expect_did(&input.did)?;
+
+    let country = input.country.to_lowercase();
+    if country.len() != 2 || !country.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(AppError::InvalidRequest(format!(
+            "Country must be a two-letter code, got {country}"
+        )));
+    }
+
+    database
+        .set_profile_country_override(&input.did, &country, ACTOR)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+pub struct ClearCountryOverrideParams {
+    did: String,
+}
+
+pub async fn clear_country_override(
+    State(database): State<Arc<Database>>,
+    params: Result<Query<ClearCountryOverrideParams>, QueryRejection>,
+) -> Result<StatusCode, AppError> {
+    let Query(params) =
+        params.map_err(|rejection| AppError::InvalidRequest(rejection.body_text()))?;
+
+    expect_did(&params.did)?;
+
+    if !database.clear_profile_country_override(&params.did).await? {
+        return Err(AppError::NotFound(format!(
+            "{} has no country override",
+            params.did
+        )));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(query: &str) -> ListProfilesParams {
+        Query::try_from_uri(&format!("/admin/profiles?{query}").parse().unwrap())
+            .unwrap()
+            .0
+    }
+
+    #[test]
+    fn parses_profile_list_params() {
+        let params = parse("country=nl&limit=5");
+        assert_eq!(params.country.as_deref(), Some("nl"));
+        assert_eq!(params.limit, Some(5));
+
+        let params = parse("");
+        assert_eq!(params.country, None);
+        assert_eq!(params.limit, None);
+    }
+}
@@ -0,0 +1,45 @@
+use std::sync::Arc;
+
+use axum::extract::State;
+use axum::Json;
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::processes::feed_server::errors::AppError;
+use crate::services::bluesky::FIREHOSE_HOST;
+use crate::services::database::IndexingStats;
+use crate::services::Database;
+
+#[derive(Serialize)]
+pub struct Stats {
+    #[serde(flatten)]
+    indexing: IndexingStats,
+    /// Sequence number of the last firehose event that was indexed
+    firehose_cursor: Option<i64>,
+    /// Sequence number of the last label that was taken from the labeler, if there's one
+    labeler_cursor: Option<i64>,
+}
+
+pub async fn stats(
+    State(database): State<Arc<Database>>,
+    State(config): State<Arc<Config>>,
+) -> Result<Json<Stats>, AppError> {
+    let firehose_cursor = database
+        .fetch_subscription_cursor(FIREHOSE_HOST, &config.feed_generator_hostname)
+        .await?;
+
+    let labeler_cursor = match &config.labeler_url {
+        Some(labeler_url) => {
+            database
+                .fetch_subscription_cursor(labeler_url, &config.feed_generator_hostname)
+                .await?
+        }
+        None => None,
+    };
+
+    Ok(Json(Stats {
+        indexing: database.fetch_indexing_stats().await?,
+        firehose_cursor,
+        labeler_cursor,
+    }))
+}
@@ -0,0 +1,77 @@
+use std::convert::Infallible;
+
+use anyhow::{anyhow, Result};
+use axum::async_trait;
+use axum::extract::FromRequestParts;
+use axum::http::header::AUTHORIZATION;
+use axum::http::request::Parts;
+use tracing::{debug, warn};
+
+use crate::services::auth::BadToken;
+
+use super::errors::AppError;
+use super::state::FeedServerState;
+
+/// DID of the user that a request was made on behalf of, if it came with a valid token.
+///
+/// Requests with a token that can't be verified are treated as anonymous, so that
+/// they can still be answered without personalization.
+pub struct Viewer(pub Option<String>);
+
+/// DID of the user that a request was made on behalf of, for requests that can't be
+/// answered anonymously.
+pub struct AuthenticatedViewer(pub String);
+
+#[async_trait]
+impl FromRequestParts<FeedServerState> for Viewer {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &FeedServerState,
+    ) -> Result<Self, Self::Rejection> {
+        match authenticate(parts, state).await {
+            Ok(did) => Ok(Viewer(did)),
+            Err(e) => {
+                if e.is::<BadToken>() {
+                    debug!("Treating a request as anonymous: {e}");
+                } else {
+                    warn!("Treating a request as anonymous: {e:?}");
+                }
+
+                Ok(Viewer(None))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<FeedServerState> for AuthenticatedViewer {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &FeedServerState,
+    ) -> Result<Self, Self::Rejection> {
+        authenticate(parts, state)
+            .await?
+            .map(AuthenticatedViewer)
+            .ok_or_else(|| AppError::AuthenticationRequired("Authentication required".to_owned()))
+    }
+}
+
+async fn authenticate(parts: &Parts, state: &FeedServerState) -> Result<Option<String>> {
+    let Some(header) = parts.headers.get(AUTHORIZATION) else {
+        return Ok(None);
+    };
+
+    let token = header
+        .to_str()
+        .ok()
+        .and_then(|header| header.strip_prefix("Bearer "))
+        .ok_or_else(|| anyhow!(BadToken("Expected a bearer token".to_owned())))?;
+
+    let method = parts.uri.path().strip_prefix("/xrpc/").unwrap_or_default();
+
+    Ok(Some(state.auth.verify(token.trim(), method).await?))
+}
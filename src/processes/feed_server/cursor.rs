@@ -0,0 +1,385 @@
+//! Cursors are opaque to clients: a version byte, a MAC over the cursor along with
+//! the feed it was issued for, and the cursor itself as DAG-CBOR, all encoded as
+//! URL-safe base64. Cursors in the `<millis>::<cid>` format used before are still
+//! accepted, so that clients paging through a feed during an upgrade don't notice.
+//!
+//! Times are kept to the microsecond, which is as precise as they're stored, so that
+//! posts indexed within the same second are never skipped or repeated across pages.
+
+use anyhow::Result;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::DateTime;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::algos::{BadCursor, Cursor, SortKey};
+
+const VERSION: u8 = 1;
+
+/// Number of leading bytes of the MAC that are kept, to keep cursors short
+const MAC_LENGTH: usize = 16;
+
+#[derive(Serialize, Deserialize)]
+enum WireKey {
+    /// Microseconds since the epoch
+    Time(i64),
+    Score(f64),
+}
+
+#[derive(Serialize, Deserialize)]
+struct WireCursor {
+    key: WireKey,
+    cid: String,
+}
+
+/// Turns cursors into strings handed out to clients and back, making sure that
+/// clients only ever send back cursors that we made for the same feed.
+pub struct CursorCodec {
+    secret: Vec<u8>,
+}
+
+impl CursorCodec {
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            secret: secret.to_vec(),
+        }
+    }
+
+    /// Creates a codec with a secret of its own, whose cursors won't be accepted by any
+    /// other codec, including the one created after a restart.
+    pub fn random() -> Self {
+        let mut secret = vec![0; 32];
+        rand::thread_rng().fill_bytes(&mut secret);
+        Self { secret }
+    }
+
+    pub fn encode(&self, feed: &str, cursor: &Cursor) -> Result<String> {
+        let wire = WireCursor {
+            key: match cursor.key {
+                SortKey::Time(time) => WireKey::Time(time.timestamp_micros()),
+                SortKey::Score(score) => WireKey::Score(score),
+            },
+            cid: cursor.cid.clone(),
+        };
+
+        let payload = serde_ipld_dagcbor::to_vec(&wire)?;
+        let tag = self.mac(feed, &payload).finalize().into_bytes();
+
+        let mut bytes = Vec::with_capacity(1 + MAC_LENGTH + payload.len());
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&tag[..MAC_LENGTH]);
+        bytes.extend_from_slice(&payload);
+
+        Ok(URL_SAFE_NO_PAD.encode(bytes))
+    }
+
+    pub fn decode(&self, feed: &str, cursor: &str) -> Result<Cursor, BadCursor> {
+        // Base64 never contains colons, so there's no mistaking one format for the other
+        if cursor.contains("::") {
+            return parse_legacy_cursor(cursor);
+        }
+
+        let malformed = || BadCursor(format!("Malformed cursor: {cursor}"));
+
+        let bytes = URL_SAFE_NO_PAD.decode(cursor).map_err(|_| malformed())?;
+        let (&version, rest) = bytes.split_first().ok_or_else(malformed)?;
+
+        if version != VERSION {
+            return Err(BadCursor(format!("Unsupported cursor version {version}")));
+        }
+
+        if rest.len() < MAC_LENGTH {
+            return Err(malformed());
+        }
+
+        let (tag, payload) = rest.split_at(MAC_LENGTH);
+
+        self.mac(feed, payload)
+            .verify_truncated_left(tag)
+            .map_err(|_| BadCursor(format!("Cursor was not issued for feed {feed}")))?;
+
+        let wire: WireCursor = serde_ipld_dagcbor::from_slice(payload).map_err(|_| malformed())?;
+
+        let key = match wire.key {
+            WireKey::Time(micros) => {
+                SortKey::Time(DateTime::from_timestamp_micros(micros).ok_or_else(malformed)?)
+            }
+            WireKey::Score(score) => SortKey::Score(score),
+        };
+
+        Ok(Cursor { key, cid: wire.cid })
+    }
+
+    fn mac(&self, feed: &str, payload: &[u8]) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret)
+            .expect("HMAC should take keys of any size");
+        mac.update(&[VERSION]);
+        mac.update(feed.as_bytes());
+        mac.update(&[0]);
+        mac.update(payload);
+        mac
+    }
+}
+
+/// Legacy cursors only kept whole seconds, so the position is moved to the end of the
+/// second to make sure that no posts are skipped, at the expense of repeating some.
+fn parse_legacy_cursor(cursor: &str) -> Result<Cursor, BadCursor> {
+    let malformed = || BadCursor(format!("Malformed cursor: {cursor}"));
+
+    let (millis, cid) = cursor.split_once("::").ok_or_else(malformed)?;
+
+    if cid.is_empty() || cid.contains("::") {
+        return Err(malformed());
+    }
+
+    let millis: i64 = millis.parse().map_err(|_| malformed())?;
+    let indexed_at =
+        DateTime::from_timestamp(millis.div_euclid(1000), 999_999_999).ok_or_else(malformed)?;
+
+    Ok(Cursor::time(indexed_at, cid))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::services::database::testing::TestDatabase;
+    use crate::services::database::FeedFilter;
+
+    const CID: &str = "bafyreib2rxk3rh6kzwq";
+
+    fn codec() -> CursorCodec {
+        CursorCodec::new(b"secret")
+    }
+
+    #[test]
+    fn round_trips_cursors() {
+        let codec = codec();
+
+        let time = Cursor::time(
+            DateTime::from_timestamp_micros(1_729_000_000_123_456).unwrap(),
+            CID,
+        );
+        let score = Cursor::score(12.5, CID);
+
+        for cursor in [time, score] {
+            let encoded = codec.encode("feed", &cursor).unwrap();
+            assert_eq!(codec.decode("feed", &encoded).unwrap(), cursor);
+        }
+    }
+
+    #[test]
+    fn rejects_cursors_from_elsewhere() {
+        let codec = codec();
+        let encoded = codec.encode("feed", &Cursor::score(1.0, CID)).unwrap();
+
+        assert!(codec.decode("other-feed", &encoded).is_err());
+        assert!(CursorCodec::new(b"other").decode("feed", &encoded).is_err());
+        assert!(CursorCodec::random().decode("feed", &encoded).is_err());
+
+        let mut tampered = URL_SAFE_NO_PAD.decode(&encoded).unwrap();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(codec
+            .decode("feed", &URL_SAFE_NO_PAD.encode(tampered))
+            .is_err());
+
+        let mut other_version = URL_SAFE_NO_PAD.decode(&encoded).unwrap();
+        other_version[0] = VERSION + 1;
+        assert!(codec
+            .decode("feed", &URL_SAFE_NO_PAD.encode(other_version))
+            .is_err());
+
+        assert!(codec.decode("feed", "").is_err());
+        assert!(codec.decode("feed", "not base64!").is_err());
+    }
+
+    #[test]
+    fn accepts_legacy_cursors() {
+        assert_eq!(
+            codec()
+                .decode("feed", &format!("1729000000000::{CID}"))
+                .unwrap(),
+            Cursor::time(
+                DateTime::from_timestamp(1_729_000_000, 999_999_999).unwrap(),
+                CID
+            )
+        );
+
+        assert!(codec().decode("feed", "1729000000000::a::b").is_err());
+        assert!(codec().decode("feed", "yesterday::a").is_err());
+        assert!(codec().decode("feed", "1729000000000::").is_err());
+    }
+
+    /// Serves a feed of the given posts page by page from memory, passing the cursor
+    /// of each page through the codec.
+    ///
+    /// The posts are ordered here by comparing Rust values, not by the database, so
+    /// this doesn't tell anything about how the queries order or compare posts, which
+    /// is what `pages_through_stored_posts_without_overlapping_or_skipping` is for.
+    fn paginate(posts: &[(DateTime<Utc>, String)], limit: usize) -> Vec<Vec<String>> {
+        let codec = codec();
+
+        let mut sorted = posts.to_vec();
+        sorted.sort_by(|a, b| b.cmp(a));
+
+        let mut pages = Vec::new();
+        let mut cursor: Option<String> = None;
+
+        loop {
+            let after = cursor
+                .as_deref()
+                .map(|cursor| codec.decode("feed", cursor).unwrap());
+            let after = after.as_ref().map(|after| after.time_and_cid().unwrap());
+
+            let page: Vec<_> = sorted
+                .iter()
+                .filter(|(time, cid)| after.is_none_or(|after| (*time, cid.as_str()) < after))
+                .take(limit)
+                .collect();
+
+            match page.last() {
+                Some((time, cid)) => {
+                    cursor = Some(codec.encode("feed", &Cursor::time(*time, cid)).unwrap());
+                    pages.push(page.iter().map(|(_, cid)| cid.clone()).collect());
+                }
+                None => return pages,
+            }
+        }
+    }
+
+    proptest! {
+        /// Only checks that cursors come out of the codec precise enough to pick up
+        /// exactly where the page before left off, which is what the codec is for.
+        #[test]
+        fn decoded_cursors_keep_pages_from_overlapping_or_skipping(
+            // Few distinct seconds and CIDs, so that ties are common
+            posts in proptest::collection::hash_set(
+                (0i64..3, 0u32..1_000_000, "[a-c]{1,3}"),
+                0..50,
+            ),
+            limit in 1usize..10,
+        ) {
+            let posts: Vec<_> = posts
+                .into_iter()
+                .map(|(secs, micros, cid)| {
+                    let time = DateTime::from_timestamp(1_729_000_000 + secs, micros * 1000).unwrap();
+                    (time, cid)
+                })
+                .collect();
+
+            let mut expected: Vec<_> = posts.clone();
+            expected.sort_by(|a, b| b.cmp(a));
+            let expected: Vec<_> = expected.into_iter().map(|(_, cid)| cid).collect();
+
+            let pages = paginate(&posts, limit);
+
+            prop_assert!(pages.iter().all(|page| page.len() <= limit));
+            prop_assert_eq!(pages.concat(), expected);
+        }
+    }
+
+    /// Stores posts in a feed, four in each of three seconds, three of which share the
+    /// exact same time, and returns them as the feed should serve them.
+    async fn store_posts(database: &TestDatabase) -> Vec<(DateTime<Utc>, String)> {
+        let mut posts = Vec::new();
+
+        for secs in 0..3 {
+            for (n, micros) in [250_000, 250_000, 250_000, 750_000].into_iter().enumerate() {
+                let time = DateTime::from_timestamp(1_729_000_000 + secs, micros * 1000).unwrap();
+                let cid = format!("bafy{secs}{n}");
+                let uri = format!("at://did:plc:author/app.bsky.feed.post/{cid}");
+
+                database
+                    .insert_post("did:plc:author", &cid, &uri, &["feed"])
+                    .await
+                    .unwrap();
+                database.rank_post_at(&uri, time).await;
+
+                posts.push((time, cid));
+            }
+        }
+
+        posts.sort_by(|a, b| b.cmp(a));
+        posts
+    }
+
+    async fn fetch_page(
+        database: &TestDatabase,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> Vec<(DateTime<Utc>, String)> {
+        let after = cursor.map(|cursor| codec().decode("feed", cursor).unwrap());
+        let earlier_than = after.as_ref().map(|after| after.time_and_cid().unwrap());
+
+        database
+            .fetch_feed_posts("feed", &FeedFilter::default(), limit, earlier_than)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|post| (post.ranked_at, post.cid))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn pages_through_stored_posts_without_overlapping_or_skipping() {
+        let Some(database) = TestDatabase::create().await else {
+            return;
+        };
+
+        let posts = store_posts(&database).await;
+
+        for limit in 1..=posts.len() {
+            let mut served = Vec::new();
+            let mut cursor: Option<String> = None;
+
+            loop {
+                let page = fetch_page(&database, cursor.as_deref(), limit).await;
+                assert!(page.len() <= limit);
+
+                let Some((time, cid)) = page.last() else {
+                    break;
+                };
+
+                cursor = Some(codec().encode("feed", &Cursor::time(*time, cid)).unwrap());
+                served.extend(page);
+            }
+
+            assert_eq!(served, posts, "pages of {limit}");
+        }
+
+        database.drop().await;
+    }
+
+    #[tokio::test]
+    async fn pages_on_from_legacy_cursors_without_skipping() {
+        let Some(database) = TestDatabase::create().await else {
+            return;
+        };
+
+        let posts = store_posts(&database).await;
+
+        // The first page ends in the middle of the second second
+        let first_page = fetch_page(&database, None, 5).await;
+        let (time, cid) = first_page.last().unwrap();
+        let legacy_cursor = format!("{}::{cid}", time.timestamp_millis());
+
+        // Everything from the start of that second on is served again, and nothing is left out
+        let expected: Vec<_> = posts
+            .iter()
+            .filter(|(post_time, _)| post_time.timestamp() <= time.timestamp())
+            .cloned()
+            .collect();
+
+        assert_eq!(
+            fetch_page(&database, Some(&legacy_cursor), posts.len()).await,
+            expected
+        );
+
+        database.drop().await;
+    }
+}
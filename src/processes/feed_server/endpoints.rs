@@ -1,9 +1,17 @@
 mod describe_feed_generator;
 mod did_json;
 mod get_feed_skeleton;
+mod healthz;
+mod metrics;
+mod readyz;
 mod root;
+mod send_interactions;
 
 pub use describe_feed_generator::describe_feed_generator;
 pub use did_json::did_json;
-pub use get_feed_skeleton::get_feed_skeleton;
+pub use get_feed_skeleton::{get_feed_skeleton, track_feed_skeleton_requests};
+pub use healthz::healthz;
+pub use metrics::prometheus_metrics;
+pub use readyz::readyz;
 pub use root::root;
+pub use send_interactions::send_interactions;
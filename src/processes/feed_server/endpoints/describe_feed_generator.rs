@@ -1,7 +1,7 @@
 use std::sync::Arc;
 
 use atrium_api::app::bsky::feed::describe_feed_generator::{
-    FeedData, OutputData as FeedGeneratorDescription,
+    FeedData, LinksData, OutputData as FeedGeneratorDescription,
 };
 use atrium_api::types::string::Did;
 use atrium_api::types::Object;
@@ -26,6 +26,14 @@ pub async fn describe_feed_generator(
             })
             .map(Object::from)
             .collect(),
-        links: None,
+        links: (config.privacy_policy_url.is_some() || config.terms_of_service_url.is_some()).then(
+            || {
+                LinksData {
+                    privacy_policy: config.privacy_policy_url.clone(),
+                    terms_of_service: config.terms_of_service_url.clone(),
+                }
+                .into()
+            },
+        ),
     })
 }
@@ -1,78 +1,242 @@
 use std::sync::Arc;
+use std::time::Instant;
 
-use anyhow::anyhow;
 use atrium_api::app::bsky::feed::defs::SkeletonFeedPostData;
 use atrium_api::app::bsky::feed::get_feed_skeleton::{
     OutputData as FeedSkeleton, ParametersData as FeedSkeletonQuery,
 };
-use atrium_api::types::{LimitedNonZeroU8, Object};
-use axum::extract::{Query, State};
+use atrium_api::types::string::{AtIdentifier, Did, Nsid, RecordKey};
+use atrium_api::types::{Collection, LimitedNonZeroU8, Object, Union, UnknownData};
+use axum::extract::rejection::QueryRejection;
+use axum::extract::{Query, Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
 use axum::Json;
-use chrono::{DateTime, TimeZone, Utc};
+use ipld_core::ipld::Ipld;
+use tracing::Span;
 
-use crate::algos::Algos;
+use crate::algos::{Algos, FeedReason};
+use crate::config::Config;
+use crate::processes::feed_server::auth::Viewer;
+use crate::processes::feed_server::cursor::CursorCodec;
 use crate::processes::feed_server::errors::AppError;
+use crate::processes::feed_server::state::FeedServerState;
+use crate::services::database::FeedFilter;
 use crate::services::Database;
 
+/// Most pinned posts that a feed can have on top of its first page, one less than the
+/// largest page that can be asked for
+const MAX_PINS: usize = 99;
+
 pub async fn get_feed_skeleton(
     State(algos): State<Arc<Algos>>,
     State(database): State<Arc<Database>>,
-    query: Query<FeedSkeletonQuery>,
+    State(cursors): State<Arc<CursorCodec>>,
+    State(config): State<Arc<Config>>,
+    Viewer(viewer_did): Viewer,
+    query: Result<Query<FeedSkeletonQuery>, QueryRejection>,
 ) -> Result<Json<FeedSkeleton>, AppError> {
-    let feed_name = query
-        .feed
-        .split('/')
-        .last()
-        .ok_or_else(|| anyhow!("Invalid feed URI"))?;
+    let query = query.map_err(|rejection| AppError::InvalidRequest(rejection.body_text()))?;
+
+    let feed_name = parse_feed_uri(&query.feed, &config.publisher_did)?;
+
+    Span::current().record("feed", feed_name);
 
     let algo = algos
         .get_by_name(feed_name)
-        .ok_or_else(|| AppError::FeedNotFound(feed_name.to_owned()))?;
+        .ok_or_else(|| AppError::UnknownFeed(query.feed.clone()))?;
+
+    let limit =
+        u8::from(query.limit.unwrap_or(
+            LimitedNonZeroU8::try_from(20).expect("this default limit should always work"),
+        ));
+    let after = query
+        .cursor
+        .as_deref()
+        .map(|cursor| cursors.decode(feed_name, cursor))
+        .transpose()?;
 
-    let limit = query
-        .limit
-        .unwrap_or(LimitedNonZeroU8::try_from(20).expect("this default limit should always work"));
-    let earlier_than = query.cursor.as_deref().map(parse_cursor).transpose()?;
+    let filter = algo.filter(viewer_did.as_deref());
 
-    let posts = algo
-        .fetch_posts(&database, limit.into(), earlier_than)
+    // Pinned posts go on top of the first page, taking up all but one of its places
+    // so that there's always a post for the next page to continue from. They're left
+    // out of every page further down, whether they fit on the first one or not.
+    let pins = database
+        .fetch_feed_pins(feed_name, &filter, MAX_PINS)
         .await?;
 
-    let feed = posts
+    let shown_pins = match after {
+        None => &pins[..pins.len().min(limit as usize - 1)],
+        Some(_) => &[],
+    };
+
+    let items = algo
+        .fetch_posts(
+            &database,
+            feed_name,
+            limit - shown_pins.len() as u8,
+            after.as_ref(),
+            &FeedFilter {
+                excluded_uris: &pins,
+                ..filter
+            },
+        )
+        .await?;
+
+    let cursor = items
+        .last()
+        .map(|item| cursors.encode(feed_name, &algo.cursor_for(&item.post)))
+        .transpose()?;
+
+    let feed = shown_pins
         .iter()
-        .map(|p| SkeletonFeedPostData {
-            post: p.uri.clone(),
-            feed_context: None,
-            reason: None,
-        })
-        .map(Object::from)
+        .map(|uri| skeleton_feed_post(uri.clone(), None, Some(FeedReason::Pin)))
+        .chain(
+            items
+                .into_iter()
+                .map(|item| skeleton_feed_post(item.post.uri, item.feed_context, item.reason)),
+        )
         .collect();
 
-    let cursor = posts.last().map(|p| make_cursor(&p.indexed_at, &p.cid));
-
     Ok(Json(FeedSkeleton { cursor, feed }))
 }
 
-fn make_cursor(date: &DateTime<Utc>, cid: &str) -> String {
-    format!("{}::{}", date.timestamp() * 1000, cid)
+/// Records how long serving feed skeletons takes and how it goes, per feed.
+pub async fn track_feed_skeleton_requests(
+    State(state): State<FeedServerState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    // Only feeds we serve get their own time series, however many are asked for
+    let feed = Query::<FeedSkeletonQuery>::try_from_uri(request.uri())
+        .ok()
+        .and_then(|Query(query)| {
+            parse_feed_uri(&query.feed, &state.config.publisher_did)
+                .ok()
+                .map(str::to_owned)
+        })
+        .filter(|name| state.algos.get_by_name(name).is_some())
+        .unwrap_or_else(|| "unknown".to_owned());
+
+    let started_at = Instant::now();
+    let response = next.run(request).await;
+
+    state
+        .metrics
+        .observe_feed_request(&feed, response.status().as_u16(), started_at.elapsed());
+
+    response
 }
 
-fn parse_cursor(cursor: &str) -> anyhow::Result<(DateTime<Utc>, &str)> {
-    let mut parts = cursor.split("::");
+fn skeleton_feed_post(
+    uri: String,
+    feed_context: Option<String>,
+    reason: Option<FeedReason>,
+) -> Object<SkeletonFeedPostData> {
+    let reason = reason.map(|reason| match reason {
+        // Newer than the lexicons that atrium-api was generated from
+        FeedReason::Pin => Union::Unknown(UnknownData {
+            r#type: "app.bsky.feed.defs#skeletonReasonPin".to_owned(),
+            data: Ipld::Map(Default::default()),
+        }),
+    });
 
-    let indexed_at = parts
-        .next()
-        .ok_or_else(|| anyhow!("Malformed cursor: {cursor}"))?;
-    let cid = parts
-        .next()
-        .ok_or_else(|| anyhow!("Malformed cursor: {cursor}"))?;
+    SkeletonFeedPostData {
+        post: uri,
+        feed_context,
+        reason,
+    }
+    .into()
+}
 
-    if parts.next().is_some() {
-        return Err(anyhow!("Malformed cursor: {cursor}"));
+/// Finds the name of the feed that the AT-URI of a feed generator record refers to,
+/// as long as the record is one of ours.
+fn parse_feed_uri<'a>(uri: &'a str, publisher_did: &Did) -> Result<&'a str, AppError> {
+    let invalid = || AppError::InvalidRequest(format!("Invalid feed URI: {uri}"));
+
+    let mut parts = uri.strip_prefix("at://").ok_or_else(invalid)?.split('/');
+
+    let (Some(authority), Some(collection), Some(rkey), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid());
+    };
+
+    let authority: AtIdentifier = authority.parse().map_err(|_| invalid())?;
+    Nsid::new(collection.to_owned()).map_err(|_| invalid())?;
+    RecordKey::new(rkey.to_owned()).map_err(|_| invalid())?;
+
+    if authority != AtIdentifier::Did(publisher_did.clone())
+        || collection != atrium_api::app::bsky::feed::Generator::NSID
+    {
+        return Err(AppError::UnknownFeed(uri.to_owned()));
+    }
+
+    Ok(rkey)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(uri: &str) -> Result<&str, AppError> {
+        parse_feed_uri(uri, &Did::new("did:plc:publisher".to_owned()).unwrap())
     }
 
-    let indexed_at: i64 = indexed_at.parse()?;
-    let indexed_at = Utc.timestamp_opt(indexed_at / 1000, 0).unwrap(); // TODO: handle error
+    #[test]
+    fn serializes_feed_items() {
+        let json = |reason| {
+            let uri = "at://did:plc:author/app.bsky.feed.post/1".to_owned();
+            serde_json::to_value(skeleton_feed_post(uri, Some("hot".to_owned()), reason)).unwrap()
+        };
 
-    Ok((indexed_at, cid))
+        assert_eq!(
+            json(None),
+            serde_json::json!({
+                "post": "at://did:plc:author/app.bsky.feed.post/1",
+                "feedContext": "hot",
+            })
+        );
+
+        assert_eq!(
+            json(Some(FeedReason::Pin))["reason"],
+            serde_json::json!({"$type": "app.bsky.feed.defs#skeletonReasonPin"})
+        );
+    }
+
+    #[test]
+    fn parses_our_feed_uris() {
+        assert!(matches!(
+            parse("at://did:plc:publisher/app.bsky.feed.generator/nederlandskie"),
+            Ok("nederlandskie")
+        ));
+    }
+
+    #[test]
+    fn rejects_feeds_of_others() {
+        for uri in [
+            "at://did:plc:someone/app.bsky.feed.generator/nederlandskie",
+            "at://publisher.bsky.social/app.bsky.feed.generator/nederlandskie",
+            "at://did:plc:publisher/app.bsky.feed.post/nederlandskie",
+        ] {
+            assert!(matches!(parse(uri), Err(AppError::UnknownFeed(_))), "{uri}");
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_uris() {
+        for uri in [
+            "nederlandskie",
+            "https://did:plc:publisher/app.bsky.feed.generator/nederlandskie",
+            "at://did:plc:publisher/app.bsky.feed.generator",
+            "at://did:plc:publisher/app.bsky.feed.generator/",
+            "at://did:plc:publisher/app.bsky.feed.generator/nederlandskie/more",
+            "at://not an identifier/app.bsky.feed.generator/nederlandskie",
+        ] {
+            assert!(
+                matches!(parse(uri), Err(AppError::InvalidRequest(_))),
+                "{uri}"
+            );
+        }
+    }
 }
@@ -0,0 +1,16 @@
+use std::sync::Arc;
+
+use axum::extract::State;
+use axum::http::header::CONTENT_TYPE;
+use axum::response::IntoResponse;
+
+use crate::services::{Database, Metrics};
+
+pub async fn prometheus_metrics(
+    State(metrics): State<Arc<Metrics>>,
+    State(database): State<Arc<Database>>,
+) -> impl IntoResponse {
+    metrics.observe_database_pool(database.size(), database.num_idle());
+
+    ([(CONTENT_TYPE, prometheus::TEXT_FORMAT)], metrics.render())
+}
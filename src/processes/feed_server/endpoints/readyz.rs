@@ -0,0 +1,59 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use chrono::Utc;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::services::health::IndexingHealth;
+use crate::services::{Database, Health};
+
+/// How long the database gets to answer before it's taken for unreachable.
+const DATABASE_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Serialize)]
+pub struct Readiness {
+    ready: bool,
+    database_reachable: bool,
+    indexing: IndexingHealth,
+}
+
+/// Reports whether the database can be reached and indexing is keeping up, answering
+/// with 503 when either isn't the case so that the instance gets restarted.
+pub async fn readyz(
+    State(database): State<Arc<Database>>,
+    State(health): State<Arc<Health>>,
+) -> (StatusCode, Json<Readiness>) {
+    let database_reachable = match tokio::time::timeout(DATABASE_TIMEOUT, database.ping()).await {
+        Ok(Ok(())) => true,
+        Ok(Err(e)) => {
+            warn!("Database is unreachable: {e:?}");
+            false
+        }
+        Err(_) => {
+            warn!("Database didn't answer within {DATABASE_TIMEOUT:?}");
+            false
+        }
+    };
+
+    let indexing = health.indexing(Utc::now());
+    let ready = database_reachable && !indexing.stalled;
+
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (
+        status,
+        Json(Readiness {
+            ready,
+            database_reachable,
+            indexing,
+        }),
+    )
+}
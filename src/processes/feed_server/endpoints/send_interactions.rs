@@ -0,0 +1,52 @@
+use std::sync::Arc;
+
+use atrium_api::app::bsky::feed::send_interactions::{InputData, OutputData};
+use axum::extract::rejection::JsonRejection;
+use axum::extract::State;
+use axum::Json;
+use tracing::debug;
+
+use crate::processes::feed_server::auth::AuthenticatedViewer;
+use crate::processes::feed_server::errors::AppError;
+use crate::services::database::Interaction;
+use crate::services::Database;
+
+/// More than the Bluesky app ever sends at once, but few enough to store in one go.
+const MAX_INTERACTIONS: usize = 1000;
+
+pub async fn send_interactions(
+    State(database): State<Arc<Database>>,
+    AuthenticatedViewer(viewer_did): AuthenticatedViewer,
+    input: Result<Json<InputData>, JsonRejection>,
+) -> Result<Json<OutputData>, AppError> {
+    let Json(input) = input.map_err(|rejection| AppError::InvalidRequest(rejection.body_text()))?;
+
+    if input.interactions.len() > MAX_INTERACTIONS {
+        return Err(AppError::InvalidRequest(format!(
+            "At most {MAX_INTERACTIONS} interactions can be sent at once"
+        )));
+    }
+
+    // Interactions without a post or an event don't tell us anything
+    let interactions: Vec<_> = input
+        .interactions
+        .into_iter()
+        .filter_map(|interaction| {
+            let interaction = interaction.data;
+
+            Some(Interaction {
+                post_uri: interaction.item?,
+                event: interaction.event?,
+                feed_context: interaction.feed_context,
+            })
+        })
+        .collect();
+
+    database
+        .insert_interactions(&viewer_did, &interactions)
+        .await?;
+
+    debug!("Stored {} interactions of {viewer_did}", interactions.len());
+
+    Ok(Json(OutputData {}))
+}
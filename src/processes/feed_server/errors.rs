@@ -1,23 +1,60 @@
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Serialize;
+use tracing::error;
 
+use crate::algos::BadCursor;
+use crate::services::auth::BadToken;
+
+use super::request_span::current_request_id;
+
+/// Errors answered in the shape that XRPC clients know how to read.
 pub enum AppError {
-    FeedNotFound(String),
-    Other(anyhow::Error),
+    UnknownFeed(String),
+    BadCursor(String),
+    InvalidRequest(String),
+    AuthenticationRequired(String),
+    NotFound(String),
+    /// Anything that's our fault rather than the client's. The details only end
+    /// up in the logs.
+    Internal(anyhow::Error),
+}
+
+#[derive(Serialize)]
+struct XrpcError {
+    error: &'static str,
+    message: String,
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        match self {
-            Self::FeedNotFound(name) => {
-                (StatusCode::NOT_FOUND, format!("Feed not found: {}", name))
-            }
-            Self::Other(e) => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Something went wrong: {}", e),
+        let (status, error, message) = match self {
+            Self::UnknownFeed(name) => (
+                StatusCode::BAD_REQUEST,
+                "UnknownFeed",
+                format!("Unknown feed: {name}"),
             ),
-        }
-        .into_response()
+            Self::BadCursor(message) => (StatusCode::BAD_REQUEST, "BadCursor", message),
+            Self::InvalidRequest(message) => (StatusCode::BAD_REQUEST, "InvalidRequest", message),
+            Self::AuthenticationRequired(message) => {
+                (StatusCode::UNAUTHORIZED, "AuthenticationRequired", message)
+            }
+            Self::NotFound(message) => (StatusCode::NOT_FOUND, "NotFound", message),
+            Self::Internal(e) => {
+                let request_id = current_request_id()
+                    .unwrap_or_else(|| format!("{:016x}", rand::random::<u64>()));
+                error!("Request {request_id} failed: {e:?}");
+
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "InternalServerError",
+                    format!("Something went wrong, request id {request_id}"),
+                )
+            }
+        };
+
+        (status, Json(XrpcError { error, message })).into_response()
     }
 }
 
@@ -26,6 +63,66 @@ where
     E: Into<anyhow::Error>,
 {
     fn from(err: E) -> Self {
-        Self::Other(err.into())
+        let err = match err.into().downcast::<BadCursor>() {
+            Ok(BadCursor(message)) => return Self::BadCursor(message),
+            Err(err) => err,
+        };
+
+        match err.downcast::<BadToken>() {
+            Ok(BadToken(message)) => Self::AuthenticationRequired(message),
+            Err(err) => Self::Internal(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+    use axum::body::to_bytes;
+
+    use super::*;
+
+    async fn respond(error: AppError) -> (StatusCode, String) {
+        let response = error.into_response();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn answers_in_xrpc_shape() {
+        assert_eq!(
+            respond(AppError::UnknownFeed("nope".to_owned())).await,
+            (
+                StatusCode::BAD_REQUEST,
+                r#"{"error":"UnknownFeed","message":"Unknown feed: nope"}"#.to_owned()
+            )
+        );
+
+        assert_eq!(
+            respond(anyhow::Error::from(BadCursor("Malformed cursor: x".to_owned())).into()).await,
+            (
+                StatusCode::BAD_REQUEST,
+                r#"{"error":"BadCursor","message":"Malformed cursor: x"}"#.to_owned()
+            )
+        );
+
+        assert_eq!(
+            respond(anyhow::Error::from(BadToken("Token has expired".to_owned())).into()).await,
+            (
+                StatusCode::UNAUTHORIZED,
+                r#"{"error":"AuthenticationRequired","message":"Token has expired"}"#.to_owned()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn hides_internal_errors() {
+        let (status, body) = respond(anyhow!("password authentication failed").into()).await;
+
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(body.starts_with(r#"{"error":"InternalServerError","#));
+        assert!(!body.contains("password"));
     }
 }
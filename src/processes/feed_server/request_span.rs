@@ -0,0 +1,51 @@
+use axum::extract::Request;
+use axum::middleware::Next;
+use axum::response::Response;
+use tracing::{field, info_span, Instrument};
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Handles every request within a span of its own, carrying an id that tells apart
+/// what was logged about it. Handlers can fill in the `feed` of the span once they
+/// know it.
+pub async fn trace_requests(request: Request, next: Next) -> Response {
+    let request_id = format!("{:016x}", rand::random::<u64>());
+
+    let span = info_span!(
+        "request",
+        request_id = %request_id,
+        method = %request.method(),
+        path = request.uri().path(),
+        feed = field::Empty,
+    );
+
+    REQUEST_ID
+        .scope(request_id, next.run(request))
+        .instrument(span)
+        .await
+}
+
+/// Id of the request being handled, if there is one.
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn knows_the_current_request() {
+        assert_eq!(current_request_id(), None);
+
+        let request_id = REQUEST_ID
+            .scope("0123456789abcdef".to_owned(), async {
+                current_request_id()
+            })
+            .await;
+
+        assert_eq!(request_id.as_deref(), Some("0123456789abcdef"));
+    }
+}
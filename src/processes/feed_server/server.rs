@@ -1,34 +1,84 @@
 use std::sync::Arc;
 
 use anyhow::Result;
-use axum::routing::get;
+use axum::middleware;
+use axum::routing::{get, post};
 use axum::Router;
-use log::info;
+use tracing::{info, warn};
 
 use crate::algos::Algos;
 use crate::config::Config;
-use crate::services::Database;
+use crate::services::{Database, Health, Metrics, ServiceAuth};
 
-use super::endpoints::{describe_feed_generator, did_json, get_feed_skeleton, root};
+use super::admin;
+use super::cursor::CursorCodec;
+use super::endpoints::{
+    describe_feed_generator, did_json, get_feed_skeleton, healthz, prometheus_metrics, readyz,
+    root, send_interactions, track_feed_skeleton_requests,
+};
+use super::request_span::trace_requests;
 use super::state::FeedServerState;
 
-pub async fn serve(database: Arc<Database>, config: Arc<Config>, algos: Arc<Algos>) -> Result<()> {
-    let app = Router::new()
+pub async fn serve(
+    database: Arc<Database>,
+    config: Arc<Config>,
+    algos: Arc<Algos>,
+    metrics: Arc<Metrics>,
+    health: Arc<Health>,
+) -> Result<()> {
+    let cursors = Arc::new(match &config.cursor_secret {
+        Some(secret) => CursorCodec::new(secret.as_bytes()),
+        None => {
+            warn!("No cursor secret configured, cursors won't be accepted after a restart");
+            CursorCodec::random()
+        }
+    });
+
+    let auth = Arc::new(ServiceAuth::new(&format!(
+        "did:web:{}",
+        config.feed_generator_hostname
+    )));
+
+    let state = FeedServerState {
+        database,
+        config: config.clone(),
+        algos,
+        cursors,
+        auth,
+        metrics,
+        health,
+    };
+
+    let mut app = Router::new()
         .route("/", get(root))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
         .route("/.well-known/did.json", get(did_json))
+        .route("/metrics", get(prometheus_metrics))
         .route(
             "/xrpc/app.bsky.feed.describeFeedGenerator",
             get(describe_feed_generator),
         )
         .route(
             "/xrpc/app.bsky.feed.getFeedSkeleton",
-            get(get_feed_skeleton),
+            get(get_feed_skeleton).route_layer(middleware::from_fn_with_state(
+                state.clone(),
+                track_feed_skeleton_requests,
+            )),
         )
-        .with_state(FeedServerState {
-            database: database,
-            config: config,
-            algos: algos,
-        });
+        .route(
+            "/xrpc/app.bsky.feed.sendInteractions",
+            post(send_interactions),
+        );
+
+    match &config.admin_token {
+        Some(token) => app = app.nest("/admin", admin::router(token)),
+        None => info!("No admin token configured, the admin API is disabled"),
+    }
+
+    let app = app
+        .layer(middleware::from_fn(trace_requests))
+        .with_state(state);
 
     let addr = "0.0.0.0:3030";
     info!("Serving feed on {}", addr);
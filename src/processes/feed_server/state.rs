@@ -4,13 +4,19 @@ use axum::extract::FromRef;
 
 use crate::algos::Algos;
 use crate::config::Config;
-use crate::services::Database;
+use crate::services::{Database, Health, Metrics, ServiceAuth};
+
+use super::cursor::CursorCodec;
 
 #[derive(Clone)]
 pub struct FeedServerState {
     pub database: Arc<Database>,
     pub config: Arc<Config>,
     pub algos: Arc<Algos>,
+    pub cursors: Arc<CursorCodec>,
+    pub auth: Arc<ServiceAuth>,
+    pub metrics: Arc<Metrics>,
+    pub health: Arc<Health>,
 }
 
 impl FromRef<FeedServerState> for Arc<Database> {
@@ -30,3 +36,21 @@ impl FromRef<FeedServerState> for Arc<Algos> {
         state.algos.clone()
     }
 }
+
+impl FromRef<FeedServerState> for Arc<CursorCodec> {
+    fn from_ref(state: &FeedServerState) -> Arc<CursorCodec> {
+        state.cursors.clone()
+    }
+}
+
+impl FromRef<FeedServerState> for Arc<Metrics> {
+    fn from_ref(state: &FeedServerState) -> Arc<Metrics> {
+        state.metrics.clone()
+    }
+}
+
+impl FromRef<FeedServerState> for Arc<Health> {
+    fn from_ref(state: &FeedServerState) -> Arc<Health> {
+        state.health.clone()
+    }
+}
@@ -0,0 +1,69 @@
+use std::pin::pin;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use tokio_stream::StreamExt;
+use tokio_tungstenite::tungstenite;
+use tracing::{debug, info};
+
+use crate::config::Config;
+use crate::services::bluesky::{self, LabelDetails};
+use crate::services::Database;
+
+/// Keeps track of the labels that the configured labeler puts on posts and authors
+/// we know, so that feeds can leave them out according to their label policies.
+pub async fn start(database: Arc<Database>, config: Arc<Config>) -> Result<()> {
+    let Some(labeler_url) = &config.labeler_url else {
+        info!("No labeler configured, only self-labels will be respected");
+        return Ok(());
+    };
+
+    let cursor = database
+        .fetch_subscription_cursor(labeler_url, &config.feed_generator_hostname)
+        .await?;
+
+    if cursor.is_none() {
+        database
+            .create_subscription_state(labeler_url, &config.feed_generator_hostname)
+            .await?;
+    }
+
+    let mut stream = pin!(bluesky::subscribe_to_labels(labeler_url, cursor)
+        .await
+        .context("failed to subscribe to labels")?
+        .timeout(bluesky::STREAMING_TIMEOUT));
+
+    while let Some(Ok(tungstenite::Message::Binary(message))) = stream.try_next().await? {
+        let Some(batch) = bluesky::handle_label_message(&message)? else {
+            continue;
+        };
+
+        for label in &batch.labels {
+            process_label(&database, label).await?;
+        }
+
+        database
+            .update_subscription_cursor(labeler_url, &config.feed_generator_hostname, batch.seq)
+            .await?;
+    }
+
+    Ok(())
+}
+
+async fn process_label(database: &Database, label: &LabelDetails) -> Result<()> {
+    if label.negated {
+        if database
+            .delete_label(&label.src, &label.uri, &label.val)
+            .await?
+        {
+            debug!("Took back label {} of {}", label.val, label.uri);
+        }
+    } else if database
+        .insert_label(&label.src, &label.uri, &label.val, label.expires_at)
+        .await?
+    {
+        debug!("Stored label {} of {}", label.val, label.uri);
+    }
+
+    Ok(())
+}
@@ -1,32 +1,102 @@
+mod engagement;
+
 use std::sync::Arc;
 
 use anyhow::Result;
 use atrium_api::types::Collection;
-use log::{debug, error, info};
-use tokio::sync::broadcast;
+use chrono::{DateTime, Utc};
+use tokio::sync::mpsc;
+use tracing::{debug, error, info, info_span, warn, Instrument};
 
 use crate::algos::Algos;
 use crate::config::Config;
-use crate::services::bluesky::{CommitDetails, Operation, FIREHOSE_HOST};
-use crate::services::Database;
+use crate::services::bluesky::{
+    CommitDetails, LikeRecord, Operation, PostFeatures, ProfileDetails, RepostRecord, FIREHOSE_HOST,
+};
+use crate::services::database::{EngagementKind, ListItem};
+use crate::services::spam::{AccountAgeCheck, SpamVerdict};
+use crate::services::{Database, Health, ListMembers, Metrics, SpamFilter};
+
+use self::engagement::{EngagementTracker, RECENT_POSTS};
 
+#[allow(clippy::too_many_arguments)]
 pub async fn start(
     database: Arc<Database>,
     config: Arc<Config>,
     algos: Arc<Algos>,
-    mut firehose: broadcast::Receiver<CommitDetails>,
+    list_members: Arc<ListMembers>,
+    spam_filter: Arc<SpamFilter>,
+    metrics: Arc<Metrics>,
+    health: Arc<Health>,
+    mut firehose: mpsc::Receiver<CommitDetails>,
+    account_checks: mpsc::Sender<AccountAgeCheck>,
 ) -> Result<()> {
-    while let Ok(commit) = firehose.recv().await {
-        process_commit(&database, &algos, &config, &commit).await?;
+    let rankings: Vec<_> = algos
+        .iter()
+        .map(|(name, algo)| (name, algo.ranking()))
+        .collect();
+
+    // Gravity of feeds might have changed since they were last ranked
+    database.rank_feed_posts(&rankings).await?;
+
+    let mut engagement = EngagementTracker::load(&database, RECENT_POSTS).await?;
+
+    while let Some(commit) = firehose.recv().await {
+        process_commit(
+            &database,
+            &algos,
+            &config,
+            &list_members,
+            &spam_filter,
+            &metrics,
+            &mut engagement,
+            &account_checks,
+            &commit,
+        )
+        .instrument(info_span!(
+            "commit",
+            seq = commit.seq,
+            did = commit_did(&commit)
+        ))
+        .await?;
+
+        metrics.observe_indexed_commit(&commit, firehose.len());
+        health.observe_indexed_commit(&commit);
+
+        if commit.seq % 20 == 0 {
+            engagement.flush(&database, &rankings).await?;
+
+            debug!(
+                "Updating cursor for {} to {} ({})",
+                config.feed_generator_hostname.as_str(),
+                commit.seq,
+                commit.time
+            );
+            database
+                .update_subscription_cursor(
+                    FIREHOSE_HOST,
+                    &config.feed_generator_hostname,
+                    commit.seq,
+                )
+                .await?;
+
+            health.observe_cursor_write(Utc::now());
+        }
     }
 
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn process_commit(
     database: &Database,
     algos: &Algos,
     config: &Config,
+    list_members: &ListMembers,
+    spam_filter: &SpamFilter,
+    metrics: &Metrics,
+    engagement: &mut EngagementTracker,
+    account_checks: &mpsc::Sender<AccountAgeCheck>,
     commit: &CommitDetails,
 ) -> Result<()> {
     for operation in &commit.operations {
@@ -34,10 +104,11 @@ async fn process_commit(
             Operation::Create {
                 collection,
                 did,
+                rkey,
                 cid,
                 block,
             } => {
-                let uri = format!("at://{}/{}/{}", did.as_str(), collection, cid);
+                let uri = format!("at://{}/{}/{}", did.as_str(), collection, rkey);
 
                 match collection.as_str() {
                     atrium_api::app::bsky::feed::Post::NSID => {
@@ -52,25 +123,156 @@ async fn process_commit(
                             }
                         };
 
-                        for algo in algos.iter_all() {
-                            if algo.should_index_post(did, &post).await? {
-                                info!("Received insertable post from {}: {post:?}", did.as_str());
+                        let features = PostFeatures::from(&*post);
+
+                        spam_filter.observe(did, &features, commit.time);
+
+                        let mut feeds = Vec::new();
+                        for (name, algo) in algos.iter() {
+                            let accepted = algo.should_index_post(did, &post, &features).await?;
 
-                                database.insert_post(did, &cid.to_string(), &uri).await?;
+                            metrics.observe_algo_decision(name, accepted);
 
-                                break;
+                            if accepted {
+                                feeds.push(name);
                             }
                         }
+
+                        if feeds.is_empty() {
+                            continue;
+                        }
+
+                        if database.is_author_banned(did).await? {
+                            debug!("Skipped a post from banned author {}", did.as_str());
+                            continue;
+                        }
+
+                        let spam_check =
+                            check_spam(database, spam_filter, did, &features, commit.time).await?;
+
+                        if let SpamCheck::Spam(verdict) = spam_check {
+                            info!(
+                                "Skipped a post from {} as spam, {}: {uri}",
+                                did.as_str(),
+                                verdict.details
+                            );
+
+                            database
+                                .record_spam_decision(
+                                    did,
+                                    &uri,
+                                    verdict.reason.as_str(),
+                                    &verdict.details,
+                                )
+                                .await?;
+
+                            continue;
+                        }
+
+                        database
+                            .insert_post(did, &cid.to_string(), &uri, &feeds)
+                            .await?;
+
+                        if spam_check == SpamCheck::AccountAgeUnknown {
+                            let check = AccountAgeCheck {
+                                did: did.to_string(),
+                                uri: uri.clone(),
+                                posted_at: commit.time,
+                            };
+
+                            if account_checks.try_send(check).is_err() {
+                                warn!("Too many accounts waiting to be checked, letting {uri} in");
+                            }
+                        }
+
+                        engagement.observe_post(&uri);
+
+                        info!(
+                            uri,
+                            ?feeds,
+                            text = config
+                                .logging
+                                .include_post_text
+                                .then_some(features.text.as_str()),
+                            "Indexed a post"
+                        );
+                    }
+                    atrium_api::app::bsky::feed::Like::NSID => {
+                        let like = match serde_ipld_dagcbor::from_slice::<LikeRecord>(&block[..]) {
+                            Ok(like) => like,
+                            Err(e) => {
+                                error!("Error deserializing a like: {:?}", e);
+                                continue;
+                            }
+                        };
+
+                        if engagement.observe_engagement(
+                            &uri,
+                            &like.subject.uri,
+                            EngagementKind::Like,
+                        ) {
+                            debug!("Counted a like of {}", like.subject.uri);
+                        }
+                    }
+                    atrium_api::app::bsky::feed::Repost::NSID => {
+                        let repost =
+                            match serde_ipld_dagcbor::from_slice::<RepostRecord>(&block[..]) {
+                                Ok(repost) => repost,
+                                Err(e) => {
+                                    error!("Error deserializing a repost: {:?}", e);
+                                    continue;
+                                }
+                            };
+
+                        if engagement.observe_engagement(
+                            &uri,
+                            &repost.subject.uri,
+                            EngagementKind::Repost,
+                        ) {
+                            debug!("Counted a repost of {}", repost.subject.uri);
+                        }
+                    }
+                    atrium_api::app::bsky::actor::Profile::NSID => {
+                        process_profile(database, did, rkey, block).await?;
+                    }
+                    atrium_api::app::bsky::graph::Listitem::NSID
+                        if did == &config.publisher_did =>
+                    {
+                        process_list_item_creation(database, list_members, &uri, block).await?;
                     }
                     _ => {}
                 }
             }
+            Operation::Update {
+                collection,
+                did,
+                rkey,
+                block,
+                ..
+            } => {
+                if collection == atrium_api::app::bsky::actor::Profile::NSID {
+                    process_profile(database, did, rkey, block).await?;
+                }
+            }
             Operation::Delete {
                 collection,
                 did,
-                cid,
+                rkey,
             } => {
-                let _uri = format!("at://{}/{}/{}", did.as_str(), collection, cid);
+                let uri = format!("at://{}/{}/{}", did.as_str(), collection, rkey);
+
+                match collection.as_str() {
+                    atrium_api::app::bsky::feed::Like::NSID
+                    | atrium_api::app::bsky::feed::Repost::NSID => {
+                        engagement.observe_deletion(&uri);
+                    }
+                    atrium_api::app::bsky::graph::Listitem::NSID
+                        if did == &config.publisher_did =>
+                    {
+                        process_list_item_deletion(database, list_members, &uri).await?;
+                    }
+                    _ => {}
+                }
 
                 // info!("Received a post to delete: {uri}");
                 // self.database.delete_post(uri).await?;
@@ -78,16 +280,142 @@ async fn process_commit(
         }
     }
 
-    if commit.seq % 20 == 0 {
-        debug!(
-            "Updating cursor for {} to {} ({})",
-            config.feed_generator_hostname.as_str(),
-            commit.seq,
-            commit.time
-        );
-        database
-            .update_subscription_cursor(FIREHOSE_HOST, &config.feed_generator_hostname, commit.seq)
-            .await?;
+    Ok(())
+}
+
+/// Commits are made to a single repository, so all of their operations are by the same author.
+fn commit_did(commit: &CommitDetails) -> Option<&str> {
+    commit.operations.first().map(|operation| match operation {
+        Operation::Create { did, .. }
+        | Operation::Update { did, .. }
+        | Operation::Delete { did, .. } => did.as_str(),
+    })
+}
+
+#[derive(Debug, PartialEq)]
+enum SpamCheck {
+    Spam(SpamVerdict),
+    NotSpam,
+    /// Nothing looks like spam, but the author's account might be too new, which
+    /// gets looked up separately so as not to hold up indexing
+    AccountAgeUnknown,
+}
+
+/// Checks whether a post that's about to go into feeds looks like spam.
+async fn check_spam(
+    database: &Database,
+    spam_filter: &SpamFilter,
+    did: &str,
+    features: &PostFeatures,
+    time: DateTime<Utc>,
+) -> Result<SpamCheck> {
+    if let Some(verdict) = spam_filter.check(did, features, time) {
+        return Ok(SpamCheck::Spam(verdict));
+    }
+
+    let Some(min_age) = spam_filter.min_account_age() else {
+        return Ok(SpamCheck::NotSpam);
+    };
+
+    let age = database.fetch_profile_age(did).await?;
+
+    // Authors we've known for long enough don't need to be looked up
+    if age
+        .as_ref()
+        .and_then(|age| age.first_seen_at)
+        .is_some_and(|first_seen_at| time - first_seen_at >= min_age)
+    {
+        return Ok(SpamCheck::NotSpam);
+    }
+
+    Ok(match age.and_then(|age| age.account_created_at) {
+        Some(created_at) => spam_filter
+            .check_account_age(created_at, time)
+            .map_or(SpamCheck::NotSpam, SpamCheck::Spam),
+        None => SpamCheck::AccountAgeUnknown,
+    })
+}
+
+/// Refreshes cached details of a profile we already know about straight from
+/// the commit block, so that they don't have to be fetched separately.
+async fn process_profile(database: &Database, did: &str, rkey: &str, block: &[u8]) -> Result<()> {
+    if rkey != "self" {
+        return Ok(());
+    }
+
+    let profile = match serde_ipld_dagcbor::from_slice::<
+        <atrium_api::app::bsky::actor::Profile as Collection>::Record,
+    >(block)
+    {
+        Ok(profile) => profile,
+        Err(e) => {
+            error!("Error deserializing a profile: {:?}", e);
+            return Ok(());
+        }
+    };
+
+    let details = ProfileDetails::from(profile.data);
+
+    if database
+        .update_profile_details(did, &details.display_name, &details.description)
+        .await?
+    {
+        debug!("Refreshed profile details of {did}");
+    }
+
+    Ok(())
+}
+
+/// Adds a member to one of our own lists.
+async fn process_list_item_creation(
+    database: &Database,
+    list_members: &ListMembers,
+    uri: &str,
+    block: &[u8],
+) -> Result<()> {
+    let item = match serde_ipld_dagcbor::from_slice::<
+        <atrium_api::app::bsky::graph::Listitem as Collection>::Record,
+    >(block)
+    {
+        Ok(item) => item,
+        Err(e) => {
+            error!("Error deserializing a list item: {:?}", e);
+            return Ok(());
+        }
+    };
+
+    let item = ListItem {
+        uri: uri.to_owned(),
+        list_uri: item.data.list,
+        subject_did: item.data.subject.to_string(),
+    };
+
+    database.insert_list_item(&item).await?;
+    list_members.add(&item.list_uri, &item.subject_did);
+
+    info!("Added {} to list {}", item.subject_did, item.list_uri);
+
+    Ok(())
+}
+
+/// Removes a member from one of our own lists, unless they're in it more than once.
+async fn process_list_item_deletion(
+    database: &Database,
+    list_members: &ListMembers,
+    uri: &str,
+) -> Result<()> {
+    let item = match database.delete_list_item(uri).await? {
+        Some(item) => item,
+        None => return Ok(()),
+    };
+
+    if !database
+        .is_list_member(&item.list_uri, &item.subject_did)
+        .await?
+    {
+        list_members.remove(&item.list_uri, &item.subject_did);
+
+        info!("Removed {} from list {}", item.subject_did, item.list_uri);
     }
 
     Ok(())
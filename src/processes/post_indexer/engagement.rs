@@ -0,0 +1,179 @@
+use std::collections::{HashMap, VecDeque};
+
+use anyhow::Result;
+
+use crate::services::database::{CountedEngagement, Engagement, EngagementKind, Ranking};
+use crate::services::Database;
+
+/// How many of the most recently indexed posts likes and reposts are counted for.
+///
+/// Ranking only really moves posts around while they're fresh, so engagement with
+/// older ones is let go of rather than looked up.
+pub const RECENT_POSTS: usize = 50_000;
+
+/// Keeps track of likes and reposts of recently indexed posts.
+///
+/// Nearly every like and repost on the firehose is of a post that none of the feeds
+/// indexed, so they're told apart in memory instead of by asking the database, and
+/// the ones that matter are written in batches.
+pub struct EngagementTracker {
+    capacity: usize,
+    /// Recently indexed posts, oldest first
+    posts: VecDeque<String>,
+    /// Engagement URIs counted for each of the recently indexed posts
+    engagements: HashMap<String, Vec<String>>,
+    /// Post URI of each counted engagement
+    engagement_posts: HashMap<String, String>,
+    added: Vec<Engagement>,
+    removed: Vec<String>,
+}
+
+impl EngagementTracker {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            posts: VecDeque::new(),
+            engagements: HashMap::new(),
+            engagement_posts: HashMap::new(),
+            added: Vec::new(),
+            removed: Vec::new(),
+        }
+    }
+
+    /// Picks up where the last run left off from what's stored in the database.
+    pub async fn load(database: &Database, capacity: usize) -> Result<Self> {
+        let (post_uris, engagements) = database.fetch_recent_engagement(capacity).await?;
+
+        let mut tracker = Self::new(capacity);
+
+        for post_uri in post_uris {
+            tracker.observe_post(&post_uri);
+        }
+
+        for CountedEngagement { uri, post_uri } in engagements {
+            tracker.track(uri, post_uri);
+        }
+
+        Ok(tracker)
+    }
+
+    /// Takes note of a post that was indexed, forgetting the oldest one if there
+    /// are too many.
+    pub fn observe_post(&mut self, uri: &str) {
+        if self.engagements.contains_key(uri) {
+            return;
+        }
+
+        if self.posts.len() == self.capacity {
+            if let Some(oldest) = self.posts.pop_front() {
+                for engagement in self.engagements.remove(&oldest).unwrap_or_default() {
+                    self.engagement_posts.remove(&engagement);
+                }
+            }
+        }
+
+        self.posts.push_back(uri.to_owned());
+        self.engagements.insert(uri.to_owned(), Vec::new());
+    }
+
+    /// Queues a like or a repost to be counted, if it's of a recently indexed post.
+    pub fn observe_engagement(&mut self, uri: &str, post_uri: &str, kind: EngagementKind) -> bool {
+        if !self.engagements.contains_key(post_uri) || self.engagement_posts.contains_key(uri) {
+            return false;
+        }
+
+        self.track(uri.to_owned(), post_uri.to_owned());
+        self.added.push(Engagement {
+            uri: uri.to_owned(),
+            post_uri: post_uri.to_owned(),
+            kind,
+        });
+
+        true
+    }
+
+    /// Queues a like or a repost that was taken back to stop being counted, if it was.
+    pub fn observe_deletion(&mut self, uri: &str) -> bool {
+        let Some(post_uri) = self.engagement_posts.remove(uri) else {
+            return false;
+        };
+
+        if let Some(engagements) = self.engagements.get_mut(&post_uri) {
+            engagements.retain(|engagement| engagement != uri);
+        }
+
+        self.removed.push(uri.to_owned());
+
+        true
+    }
+
+    /// Writes whatever was queued up to the database, ranking the posts anew in
+    /// feeds with the given rankings.
+    pub async fn flush(&mut self, database: &Database, rankings: &[(&str, Ranking)]) -> Result<()> {
+        if self.added.is_empty() && self.removed.is_empty() {
+            return Ok(());
+        }
+
+        database
+            .update_engagement(&self.added, &self.removed, rankings)
+            .await?;
+
+        self.added.clear();
+        self.removed.clear();
+
+        Ok(())
+    }
+
+    fn track(&mut self, uri: String, post_uri: String) {
+        if let Some(engagements) = self.engagements.get_mut(&post_uri) {
+            engagements.push(uri.clone());
+            self.engagement_posts.insert(uri, post_uri);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_counts_engagement_with_recent_posts() {
+        let mut tracker = EngagementTracker::new(2);
+
+        tracker.observe_post("at://a/post/1");
+        assert!(tracker.observe_engagement("at://b/like/1", "at://a/post/1", EngagementKind::Like));
+        assert!(!tracker.observe_engagement(
+            "at://b/like/2",
+            "at://a/post/9",
+            EngagementKind::Like
+        ));
+
+        // The same like twice is only counted once
+        assert!(!tracker.observe_engagement(
+            "at://b/like/1",
+            "at://a/post/1",
+            EngagementKind::Like
+        ));
+
+        tracker.observe_post("at://a/post/2");
+        assert!(tracker.observe_engagement(
+            "at://b/repost/1",
+            "at://a/post/2",
+            EngagementKind::Repost
+        ));
+        assert!(tracker.observe_deletion("at://b/repost/1"));
+        assert!(!tracker.observe_deletion("at://b/repost/1"));
+
+        // The first post gets forgotten, along with its likes
+        tracker.observe_post("at://a/post/3");
+        assert!(!tracker.observe_engagement(
+            "at://b/like/3",
+            "at://a/post/1",
+            EngagementKind::Like
+        ));
+        assert!(!tracker.observe_deletion("at://b/like/1"));
+
+        assert_eq!(tracker.added.len(), 2);
+        assert_eq!(tracker.removed, vec!["at://b/repost/1"]);
+    }
+}
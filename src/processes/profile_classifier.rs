@@ -0,0 +1,101 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use tracing::{error, info, warn};
+
+use crate::services::database::Profile;
+use crate::services::{Bluesky, Database, Metrics, AI};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How many profiles are classified between polls, so that a backlog isn't
+/// loaded all at once.
+const BATCH_SIZE: usize = 100;
+
+/// Infers the likely country of living of every profile that hasn't been processed yet.
+///
+/// Details cached from the firehose are used whenever they're available, so Bluesky
+/// only gets asked about profiles whose details we haven't seen yet.
+pub async fn start(
+    database: Arc<Database>,
+    ai: Arc<AI>,
+    bluesky: Arc<Bluesky>,
+    metrics: Arc<Metrics>,
+) -> Result<()> {
+    loop {
+        if let Err(e) = classify_unprocessed_profiles(&database, &ai, &bluesky, &metrics).await {
+            error!("Error classifying profiles: {e:?}");
+        }
+
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+async fn classify_unprocessed_profiles(
+    database: &Database,
+    ai: &AI,
+    bluesky: &Bluesky,
+    metrics: &Metrics,
+) -> Result<()> {
+    let profiles = database.fetch_unprocessed_profiles(BATCH_SIZE).await?;
+
+    for profile in profiles {
+        let did = profile.did.clone();
+
+        // A profile that can't be classified shouldn't hold up the rest
+        if let Err(e) = classify_profile(database, ai, bluesky, metrics, profile).await {
+            warn!("Failed to classify profile {did}: {e:?}");
+
+            database.mark_profile_classification_failed(&did).await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn classify_profile(
+    database: &Database,
+    ai: &AI,
+    bluesky: &Bluesky,
+    metrics: &Metrics,
+    profile: Profile,
+) -> Result<()> {
+    let details = match (profile.display_name, profile.description) {
+        (Some(display_name), Some(description)) => Some((display_name, description)),
+        _ => bluesky
+            .fetch_profile_details(&profile.did)
+            .await
+            .context("failed to fetch profile details")?
+            .map(|details| (details.display_name, details.description)),
+    };
+
+    let country = match details {
+        Some((display_name, description)) => {
+            database
+                .update_profile_details(&profile.did, &display_name, &description)
+                .await?;
+
+            let started_at = Instant::now();
+            let country = ai
+                .infer_country_of_living(&display_name, &description)
+                .await;
+
+            metrics.observe_ai_request(started_at.elapsed(), country.is_ok());
+
+            country.context("failed to infer country of living")?
+        }
+        None => "xx".to_owned(),
+    };
+
+    database
+        .store_profile_country(&profile.did, &country)
+        .await?;
+
+    info!(
+        "Stored inferred country of living for {}: {country}",
+        profile.did
+    );
+
+    Ok(())
+}
@@ -1,7 +1,21 @@
 mod ai;
+pub mod auth;
 pub mod bluesky;
 pub mod database;
+pub mod health;
+pub mod language;
+pub mod lists;
+pub mod metrics;
+pub mod plc;
+pub mod spam;
 
 pub use ai::AI;
+pub use auth::ServiceAuth;
 pub use bluesky::Bluesky;
 pub use database::Database;
+pub use health::Health;
+pub use language::LanguageDetector;
+pub use lists::ListMembers;
+pub use metrics::Metrics;
+pub use plc::PlcDirectory;
+pub use spam::SpamFilter;
@@ -0,0 +1,522 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::Utc;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+use super::plc::PLC_DIRECTORY;
+
+/// How long a signing key of a user is trusted before it's resolved again.
+const KEY_CACHE_DURATION: Duration = Duration::from_secs(60 * 60);
+
+/// How old a cached signing key has to be to be resolved again when a token doesn't
+/// match it, in case the user rotated their keys in the meantime.
+const KEY_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long resolving a signing key may take, since it holds up answering the request.
+const RESOLVE_TIMEOUT: Duration = Duration::from_secs(5);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How many signing keys may be resolved within [`RESOLUTION_WINDOW`], so that tokens
+/// of made up issuers can't have us make requests on their behalf at will.
+const MAX_RESOLUTIONS: u32 = 100;
+const RESOLUTION_WINDOW: Duration = Duration::from_secs(60);
+
+/// Token types that are used for talking to a PDS, never for service auth.
+const FORBIDDEN_TOKEN_TYPES: &[&str] = &["at+jwt", "refresh+jwt", "dpop+jwt"];
+
+/// Reason that a token wasn't accepted, which is the fault of whoever sent it.
+#[derive(Debug)]
+pub struct BadToken(pub String);
+
+impl fmt::Display for BadToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for BadToken {}
+
+/// Verifies the tokens that Bluesky signs on behalf of its users when calling our
+/// service for them, see https://docs.bsky.app/docs/advanced-guides/service-auth
+pub struct ServiceAuth {
+    service_did: String,
+    client: reqwest::Client,
+    signing_keys: RwLock<HashMap<String, (SigningKey, Instant)>>,
+    /// Start of the current resolution window and how many keys were resolved in it
+    resolutions: Mutex<(Instant, u32)>,
+}
+
+impl ServiceAuth {
+    pub fn new(service_did: &str) -> Self {
+        Self {
+            service_did: service_did.to_owned(),
+            client: reqwest::Client::builder()
+                .timeout(RESOLVE_TIMEOUT)
+                .connect_timeout(CONNECT_TIMEOUT)
+                .dns_resolver(Arc::new(PublicResolver))
+                .build()
+                .expect("HTTP client should be buildable"),
+            signing_keys: Default::default(),
+            resolutions: Mutex::new((Instant::now(), 0)),
+        }
+    }
+
+    /// Verifies a token sent along with a call of the given XRPC method, returning
+    /// the DID of the user that it was issued by.
+    ///
+    /// Tokens that aren't acceptable result in a [`BadToken`] error, while any other
+    /// error means that we failed to find out.
+    pub async fn verify(&self, token: &str, method: &str) -> Result<String> {
+        let token = Token::parse(token)?;
+        token.check(&self.service_did, method, Utc::now().timestamp())?;
+
+        let did = token.issuer_did();
+
+        let key = self.signing_key(did, KEY_CACHE_DURATION).await?;
+        if token.verify(&key).is_err() {
+            let key = self.signing_key(did, KEY_REFRESH_INTERVAL).await?;
+            token.verify(&key)?;
+        }
+
+        Ok(did.to_owned())
+    }
+
+    async fn signing_key(&self, did: &str, max_age: Duration) -> Result<SigningKey> {
+        if let Some((key, resolved_at)) = self
+            .signing_keys
+            .read()
+            .expect("signing keys lock is poisoned")
+            .get(did)
+        {
+            if resolved_at.elapsed() < max_age {
+                return Ok(key.clone());
+            }
+        }
+
+        self.count_resolution()?;
+
+        let key = self.resolve_signing_key(did).await?;
+
+        let mut signing_keys = self
+            .signing_keys
+            .write()
+            .expect("signing keys lock is poisoned");
+        signing_keys.retain(|_, (_, resolved_at)| resolved_at.elapsed() < KEY_CACHE_DURATION);
+        signing_keys.insert(did.to_owned(), (key.clone(), Instant::now()));
+
+        Ok(key)
+    }
+
+    fn count_resolution(&self) -> Result<(), BadToken> {
+        let mut resolutions = self
+            .resolutions
+            .lock()
+            .expect("resolutions lock is poisoned");
+
+        if resolutions.0.elapsed() >= RESOLUTION_WINDOW {
+            *resolutions = (Instant::now(), 0);
+        }
+
+        if resolutions.1 >= MAX_RESOLUTIONS {
+            return Err(BadToken("Too many issuers were resolved lately".to_owned()));
+        }
+
+        resolutions.1 += 1;
+
+        Ok(())
+    }
+
+    async fn resolve_signing_key(&self, did: &str) -> Result<SigningKey> {
+        let url = if did.starts_with("did:plc:") {
+            format!("{PLC_DIRECTORY}/{did}")
+        } else if let Some(host) = did
+            .strip_prefix("did:web:")
+            .filter(|host| is_public_domain_name(host))
+        {
+            format!("https://{host}/.well-known/did.json")
+        } else {
+            return Err(BadToken(format!("Unsupported issuer: {did}")).into());
+        };
+
+        let response = self.client.get(&url).send().await?;
+        if matches!(response.status(), StatusCode::NOT_FOUND | StatusCode::GONE) {
+            return Err(BadToken(format!("Unknown issuer: {did}")).into());
+        }
+
+        let document: DidDocument = response.error_for_status()?.json().await?;
+        if document.id != did {
+            return Err(BadToken(format!("{url} doesn't describe {did}")).into());
+        }
+
+        Ok(document.signing_key()?)
+    }
+}
+
+/// Whether the host of a `did:web` is a domain name that's meant to be reachable from
+/// the internet, rather than an IP address, a port or something on the local network.
+fn is_public_domain_name(host: &str) -> bool {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+
+    host.contains('.')
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+        && host.parse::<IpAddr>().is_err()
+        && host != "localhost"
+        && !host.ends_with(".localhost")
+        && !host.ends_with(".local")
+        && !host.ends_with(".internal")
+}
+
+/// Whether an IP address can be reached over the internet, rather than only from
+/// this machine or the network that it's in.
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                // Shared address space of carrier-grade NAT
+                || (a == 100 && (64..128).contains(&b))
+                || a == 0
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ip(IpAddr::V4(ip)),
+            None => {
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local())
+            }
+        },
+    }
+}
+
+/// Resolves host names only to public IP addresses, so that a `did:web` whose domain
+/// points into our own network can't have us make requests there.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| is_public_ip(addr.ip()))
+                .collect();
+
+            if addrs.is_empty() {
+                return Err(format!("{} has no public addresses", name.as_str()).into());
+            }
+
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DidDocument {
+    id: String,
+    #[serde(default)]
+    verification_method: Vec<VerificationMethod>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VerificationMethod {
+    id: String,
+    public_key_multibase: Option<String>,
+}
+
+impl DidDocument {
+    fn signing_key(&self) -> Result<SigningKey, BadToken> {
+        let full_id = format!("{}#atproto", self.id);
+
+        self.verification_method
+            .iter()
+            .find(|method| method.id == "#atproto" || method.id == full_id)
+            .and_then(|method| method.public_key_multibase.as_deref())
+            .ok_or_else(|| BadToken(format!("{} has no signing key", self.id)))
+            .and_then(SigningKey::from_multikey)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum SigningKey {
+    K256(k256::ecdsa::VerifyingKey),
+    P256(p256::ecdsa::VerifyingKey),
+}
+
+impl SigningKey {
+    /// Reads a public key in the multibase-encoded multikey format of DID documents.
+    fn from_multikey(multikey: &str) -> Result<Self, BadToken> {
+        let unsupported = || BadToken(format!("Unsupported signing key: {multikey}"));
+
+        let (_, bytes) = multibase::decode(multikey).map_err(|_| unsupported())?;
+
+        match bytes.as_slice() {
+            [0xe7, 0x01, key @ ..] => {
+                k256::ecdsa::VerifyingKey::from_sec1_bytes(key).map(Self::K256)
+            }
+            [0x80, 0x24, key @ ..] => {
+                p256::ecdsa::VerifyingKey::from_sec1_bytes(key).map(Self::P256)
+            }
+            _ => return Err(unsupported()),
+        }
+        .map_err(|_| unsupported())
+    }
+}
+
+#[derive(Deserialize)]
+struct Header {
+    alg: String,
+    typ: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Claims {
+    iss: String,
+    aud: String,
+    exp: i64,
+    /// XRPC method that the token may be used for, if it's restricted to one
+    lxm: Option<String>,
+}
+
+struct Token<'a> {
+    header: Header,
+    claims: Claims,
+    signed_part: &'a str,
+    signature: Vec<u8>,
+}
+
+impl<'a> Token<'a> {
+    fn parse(token: &'a str) -> Result<Self, BadToken> {
+        let malformed = || BadToken("Malformed token".to_owned());
+
+        let (signed_part, signature) = token.rsplit_once('.').ok_or_else(malformed)?;
+        let (header, claims) = signed_part.split_once('.').ok_or_else(malformed)?;
+
+        let decode = |part: &str| URL_SAFE_NO_PAD.decode(part).map_err(|_| malformed());
+
+        Ok(Self {
+            header: serde_json::from_slice(&decode(header)?).map_err(|_| malformed())?,
+            claims: serde_json::from_slice(&decode(claims)?).map_err(|_| malformed())?,
+            signed_part,
+            signature: decode(signature)?,
+        })
+    }
+
+    /// Checks everything about the token besides its signature.
+    fn check(&self, service_did: &str, method: &str, now: i64) -> Result<(), BadToken> {
+        if let Some(typ) = self
+            .header
+            .typ
+            .as_deref()
+            .filter(|typ| FORBIDDEN_TOKEN_TYPES.contains(typ))
+        {
+            return Err(BadToken(format!("Unexpected token type: {typ}")));
+        }
+
+        // The audience may also point to our specific service within the DID document
+        let audience = self.claims.aud.split('#').next().unwrap_or_default();
+        if audience != service_did {
+            return Err(BadToken(format!("Token is meant for {}", self.claims.aud)));
+        }
+
+        if self.claims.exp <= now {
+            return Err(BadToken("Token has expired".to_owned()));
+        }
+
+        if let Some(lxm) = self.claims.lxm.as_deref().filter(|lxm| *lxm != method) {
+            return Err(BadToken(format!("Token is meant for calling {lxm}")));
+        }
+
+        Ok(())
+    }
+
+    /// DID of the user that the token was issued by, without the service it may point to.
+    fn issuer_did(&self) -> &str {
+        self.claims.iss.split('#').next().unwrap_or_default()
+    }
+
+    fn verify(&self, key: &SigningKey) -> Result<(), BadToken> {
+        use k256::ecdsa::signature::Verifier;
+
+        let message = self.signed_part.as_bytes();
+
+        let is_valid = match (key, self.header.alg.as_str()) {
+            (SigningKey::K256(key), "ES256K") => {
+                k256::ecdsa::Signature::from_slice(&self.signature)
+                    .is_ok_and(|signature| key.verify(message, &signature).is_ok())
+            }
+            (SigningKey::P256(key), "ES256") => p256::ecdsa::Signature::from_slice(&self.signature)
+                .is_ok_and(|signature| key.verify(message, &signature).is_ok()),
+            _ => false,
+        };
+
+        if is_valid {
+            Ok(())
+        } else {
+            Err(BadToken("Invalid token signature".to_owned()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use k256::ecdsa::signature::Signer;
+    use serde_json::json;
+
+    use super::*;
+
+    const SERVICE_DID: &str = "did:web:feeds.example.com";
+    const METHOD: &str = "app.bsky.feed.getFeedSkeleton";
+    const NOW: i64 = 1_700_000_000;
+
+    fn secret_key() -> k256::ecdsa::SigningKey {
+        k256::ecdsa::SigningKey::from_slice(&[7; 32]).unwrap()
+    }
+
+    fn multikey(key: &k256::ecdsa::SigningKey) -> String {
+        let mut bytes = vec![0xe7, 0x01];
+        bytes.extend_from_slice(key.verifying_key().to_encoded_point(true).as_bytes());
+
+        multibase::encode(multibase::Base::Base58Btc, bytes)
+    }
+
+    fn sign(header: serde_json::Value, claims: serde_json::Value) -> String {
+        let encode = |value: serde_json::Value| URL_SAFE_NO_PAD.encode(value.to_string());
+        let signed_part = format!("{}.{}", encode(header), encode(claims));
+        let signature: k256::ecdsa::Signature = secret_key().sign(signed_part.as_bytes());
+
+        format!(
+            "{signed_part}.{}",
+            URL_SAFE_NO_PAD.encode(signature.to_bytes())
+        )
+    }
+
+    fn claims() -> serde_json::Value {
+        json!({
+            "iss": "did:plc:viewer",
+            "aud": SERVICE_DID,
+            "exp": NOW + 60,
+            "lxm": METHOD,
+        })
+    }
+
+    fn verify(token: &str) -> Result<(), BadToken> {
+        let key = SigningKey::from_multikey(&multikey(&secret_key()))?;
+        let token = Token::parse(token)?;
+
+        token.check(SERVICE_DID, METHOD, NOW)?;
+        token.verify(&key)
+    }
+
+    #[test]
+    fn reads_multikeys() {
+        let key = SigningKey::from_multikey(&multikey(&secret_key())).unwrap();
+        assert_eq!(key, SigningKey::K256(*secret_key().verifying_key()));
+
+        let p256_key = p256::ecdsa::SigningKey::from_slice(&[7; 32]).unwrap();
+        let mut bytes = vec![0x80, 0x24];
+        bytes.extend_from_slice(p256_key.verifying_key().to_encoded_point(true).as_bytes());
+        assert_eq!(
+            SigningKey::from_multikey(&multibase::encode(multibase::Base::Base58Btc, bytes))
+                .unwrap(),
+            SigningKey::P256(*p256_key.verifying_key())
+        );
+
+        assert!(SigningKey::from_multikey("not a key").is_err());
+        assert!(SigningKey::from_multikey("z111111").is_err());
+    }
+
+    #[test]
+    fn only_resolves_public_hosts() {
+        assert!(is_public_domain_name("feeds.example.com"));
+        assert!(is_public_domain_name("Example.COM."));
+
+        assert!(!is_public_domain_name("localhost"));
+        assert!(!is_public_domain_name("printer.localhost"));
+        assert!(!is_public_domain_name("intranet"));
+        assert!(!is_public_domain_name("nas.local"));
+        assert!(!is_public_domain_name("127.0.0.1"));
+        assert!(!is_public_domain_name("10.0.0.1"));
+        assert!(!is_public_domain_name("example.com%3A8080"));
+        assert!(!is_public_domain_name("example.com/path"));
+
+        let public = |ip: &str| is_public_ip(ip.parse().unwrap());
+
+        assert!(public("93.184.216.34"));
+        assert!(public("2606:2800:220:1::1"));
+
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:192.168.1.1",
+        ] {
+            assert!(!public(ip), "{ip} is not public");
+        }
+    }
+
+    #[test]
+    fn accepts_valid_tokens() {
+        let token = sign(json!({"alg": "ES256K", "typ": "JWT"}), claims());
+        assert!(verify(&token).is_ok());
+        assert_eq!(Token::parse(&token).unwrap().issuer_did(), "did:plc:viewer");
+
+        let mut unrestricted = claims();
+        unrestricted["lxm"] = serde_json::Value::Null;
+        unrestricted["aud"] = json!(format!("{SERVICE_DID}#bsky_fg"));
+        assert!(verify(&sign(json!({"alg": "ES256K"}), unrestricted)).is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_tokens() {
+        let header = json!({"alg": "ES256K"});
+
+        let with = |key: &str, value: serde_json::Value| {
+            let mut claims = claims();
+            claims[key] = value;
+            sign(header.clone(), claims)
+        };
+
+        assert!(verify(&with("aud", json!("did:web:elsewhere.example.com"))).is_err());
+        assert!(verify(&with("exp", json!(NOW))).is_err());
+        assert!(verify(&with("lxm", json!("app.bsky.feed.sendInteractions"))).is_err());
+        assert!(verify(&sign(json!({"alg": "ES256K", "typ": "at+jwt"}), claims())).is_err());
+        assert!(verify(&sign(json!({"alg": "ES256"}), claims())).is_err());
+
+        let token = sign(header.clone(), claims());
+        let (signed_part, _) = token.rsplit_once('.').unwrap();
+        let tampered = format!("{signed_part}.{}", URL_SAFE_NO_PAD.encode([1; 64]));
+        assert!(verify(&tampered).is_err());
+
+        assert!(verify("not.a.token").is_err());
+        assert!(verify("").is_err());
+    }
+}
@@ -4,8 +4,12 @@ mod internals;
 mod streaming;
 
 pub use client::Bluesky;
-pub use entities::{FollowRecord, LikeRecord, PostRecord};
+pub use entities::{
+    blob_cid, detect_facets, ContentMode, ExternalLink, FeedGenerator, FollowRecord, LikeRecord,
+    ListItem, PostFeatures, PostRecord, ProfileDetails, PublishedFeedGenerator, ReplyTo,
+    RepostRecord, TextFacet, TextFacetKind,
+};
 pub use streaming::{
-    handle_message, subscribe_to_operations, CommitDetails, Operation, FIREHOSE_HOST,
-    STREAMING_TIMEOUT,
+    handle_label_message, handle_message, subscribe_to_labels, subscribe_to_operations,
+    CommitDetails, LabelDetails, LabelsDetails, Operation, FIREHOSE_HOST, STREAMING_TIMEOUT,
 };
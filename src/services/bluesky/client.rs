@@ -3,12 +3,34 @@ use std::matches;
 
 use anyhow::Result;
 use atrium_api::agent::{store::MemorySessionStore, AtpAgent};
-use atrium_api::types::string::Datetime;
-use atrium_api::types::{BlobRef, Collection, Object, TryIntoUnknown};
+use atrium_api::app::bsky::richtext::facet;
+use atrium_api::com::atproto::repo::list_records;
+use atrium_api::types::string::{Datetime, Nsid};
+use atrium_api::types::{BlobRef, Collection, Object, TryFromUnknown, TryIntoUnknown, Union};
 use atrium_xrpc_client::reqwest::ReqwestClient;
 use http::StatusCode;
+use serde::{Deserialize, Serialize};
 
-use super::entities::ProfileDetails;
+use super::entities::{
+    detect_facets, ContentMode, FeedGenerator, ListItem, ProfileDetails, PublishedFeedGenerator,
+    TextFacetKind,
+};
+
+/// A feed generator record, along with the fields that atrium doesn't know about yet
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GeneratorRecord {
+    #[serde(flatten)]
+    data: atrium_api::app::bsky::feed::generator::RecordData,
+    content_mode: &'static str,
+}
+
+/// Fields of a feed generator record that atrium doesn't know about yet
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeneratorRecordExtras {
+    content_mode: Option<String>,
+}
 
 pub struct Bluesky {
     agent: AtpAgent<MemorySessionStore, ReqwestClient>,
@@ -42,17 +64,25 @@ impl Bluesky {
         Ok(result.data.blob)
     }
 
+    /// Creates or replaces the feed generator record of a feed.
+    ///
+    /// If the CID of the record being replaced is given, nothing is replaced
+    /// unless the record is still the same.
     pub async fn publish_feed(
         &self,
         publisher_did: &str,
         feed_generator_did: &str,
         name: &str,
-        display_name: &str,
-        description: &str,
-        avatar: Option<BlobRef>,
+        feed: &FeedGenerator,
+        swap_record: Option<&str>,
     ) -> Result<()> {
         use atrium_api::com::atproto::repo::put_record::InputData;
 
+        let description_facets = match &feed.description {
+            Some(description) => Some(self.description_facets(description).await?),
+            None => None,
+        };
+
         self.agent
             .api
             .com
@@ -61,21 +91,27 @@ impl Bluesky {
             .put_record(
                 InputData {
                     collection: atrium_api::app::bsky::feed::Generator::nsid(),
-                    record: atrium_api::app::bsky::feed::generator::RecordData {
-                        avatar,
-                        created_at: Datetime::now(),
-                        description: Some(description.to_owned()),
-                        description_facets: None,
-                        did: feed_generator_did.parse().map_err(anyhow::Error::msg)?,
-                        display_name: display_name.to_owned(),
-                        labels: None,
-                        accepts_interactions: None,
+                    record: GeneratorRecord {
+                        data: atrium_api::app::bsky::feed::generator::RecordData {
+                            avatar: feed.avatar.clone(),
+                            created_at: Datetime::now(),
+                            description: feed.description.clone(),
+                            description_facets,
+                            did: feed_generator_did.parse().map_err(anyhow::Error::msg)?,
+                            display_name: feed.display_name.clone(),
+                            labels: None,
+                            accepts_interactions: Some(feed.accepts_interactions),
+                        },
+                        content_mode: feed.content_mode.as_str(),
                     }
                     .try_into_unknown()?,
                     repo: publisher_did.parse().map_err(anyhow::Error::msg)?,
                     rkey: name.to_owned(),
                     swap_commit: None,
-                    swap_record: None,
+                    swap_record: swap_record
+                        .map(str::parse)
+                        .transpose()
+                        .map_err(anyhow::Error::msg)?,
                     validate: None,
                 }
                 .into(),
@@ -85,6 +121,106 @@ impl Bluesky {
         Ok(())
     }
 
+    /// Deletes the feed generator record of a feed, unless it changed since it was fetched.
+    pub async fn unpublish_feed(
+        &self,
+        publisher_did: &str,
+        name: &str,
+        swap_record: &str,
+    ) -> Result<()> {
+        use atrium_api::com::atproto::repo::delete_record::InputData;
+
+        self.agent
+            .api
+            .com
+            .atproto
+            .repo
+            .delete_record(
+                InputData {
+                    collection: atrium_api::app::bsky::feed::Generator::nsid(),
+                    repo: publisher_did.parse().map_err(anyhow::Error::msg)?,
+                    rkey: name.to_owned(),
+                    swap_commit: None,
+                    swap_record: Some(swap_record.parse().map_err(anyhow::Error::msg)?),
+                }
+                .into(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Fetches every feed generator record in the given repo.
+    pub async fn fetch_feed_generators(&self, repo: &str) -> Result<Vec<PublishedFeedGenerator>> {
+        use atrium_api::app::bsky::feed::generator::RecordData as GeneratorRecordData;
+
+        let mut generators = Vec::new();
+
+        for record in self
+            .list_all_records(repo, atrium_api::app::bsky::feed::Generator::nsid())
+            .await?
+        {
+            let extras = GeneratorRecordExtras::try_from_unknown(record.value.clone())?;
+            let generator = GeneratorRecordData::try_from_unknown(record.value.clone())?;
+
+            generators.push(PublishedFeedGenerator {
+                name: record.uri.rsplit('/').next().unwrap_or_default().to_owned(),
+                uri: record.uri.clone(),
+                cid: record.cid.as_ref().to_string(),
+                did: generator.did.to_string(),
+                feed: FeedGenerator {
+                    display_name: generator.display_name,
+                    description: generator.description,
+                    avatar: generator.avatar,
+                    content_mode: ContentMode::from_record(extras.content_mode.as_deref()),
+                    accepts_interactions: generator.accepts_interactions.unwrap_or(false),
+                },
+            });
+        }
+
+        Ok(generators)
+    }
+
+    /// Turns links, mentions and hashtags in a description into facets, skipping
+    /// mentions of handles that don't resolve.
+    async fn description_facets(&self, description: &str) -> Result<Vec<facet::Main>> {
+        let mut facets = Vec::new();
+
+        for facet in detect_facets(description) {
+            let feature = match facet.kind {
+                TextFacetKind::Link(uri) => {
+                    facet::MainFeaturesItem::Link(Box::new(facet::LinkData { uri }.into()))
+                }
+                TextFacetKind::Tag(tag) => {
+                    facet::MainFeaturesItem::Tag(Box::new(facet::TagData { tag }.into()))
+                }
+                TextFacetKind::Mention(handle) => match self.resolve_handle(&handle).await? {
+                    Some(did) => facet::MainFeaturesItem::Mention(Box::new(
+                        facet::MentionData {
+                            did: did.parse().map_err(anyhow::Error::msg)?,
+                        }
+                        .into(),
+                    )),
+                    None => continue,
+                },
+            };
+
+            facets.push(
+                facet::MainData {
+                    features: vec![Union::Refs(feature)],
+                    index: facet::ByteSliceData {
+                        byte_start: facet.start,
+                        byte_end: facet.end,
+                    }
+                    .into(),
+                }
+                .into(),
+            );
+        }
+
+        Ok(facets)
+    }
+
     pub async fn fetch_profile_details(&self, did: &str) -> Result<Option<ProfileDetails>> {
         use atrium_api::com::atproto::repo::get_record::ParametersData;
 
@@ -114,6 +250,71 @@ impl Bluesky {
         Ok(Some(ProfileDetails::try_from(profile_output.data.value)?))
     }
 
+    /// Fetches every list item record in the given repo, whichever list it belongs to.
+    pub async fn fetch_list_items(&self, repo: &str) -> Result<Vec<ListItem>> {
+        use atrium_api::app::bsky::graph::listitem::RecordData as ListItemRecordData;
+
+        let mut items = Vec::new();
+
+        for record in self
+            .list_all_records(repo, atrium_api::app::bsky::graph::Listitem::nsid())
+            .await?
+        {
+            let item = ListItemRecordData::try_from_unknown(record.value.clone())?;
+
+            items.push(ListItem {
+                uri: record.uri.clone(),
+                list: item.list,
+                subject: item.subject.to_string(),
+            });
+        }
+
+        Ok(items)
+    }
+
+    /// Fetches every record of a collection in the given repo, page by page.
+    async fn list_all_records(
+        &self,
+        repo: &str,
+        collection: Nsid,
+    ) -> Result<Vec<list_records::Record>> {
+        let mut records = Vec::new();
+        let mut cursor = None;
+
+        loop {
+            let output = self
+                .agent
+                .api
+                .com
+                .atproto
+                .repo
+                .list_records(
+                    list_records::ParametersData {
+                        collection: collection.clone(),
+                        cursor,
+                        limit: Some(100.try_into().map_err(anyhow::Error::msg)?),
+                        repo: repo.parse().map_err(anyhow::Error::msg)?,
+                        reverse: None,
+                        rkey_end: None,
+                        rkey_start: None,
+                    }
+                    .into(),
+                )
+                .await?;
+
+            let is_last_page = output.records.is_empty() || output.cursor.is_none();
+
+            cursor = output.cursor.clone();
+            records.extend(output.data.records);
+
+            if is_last_page {
+                break;
+            }
+        }
+
+        Ok(records)
+    }
+
     pub async fn resolve_handle(&self, handle: &str) -> Result<Option<String>> {
         use atrium_api::com::atproto::identity::resolve_handle::ParametersData;
 
@@ -1,19 +1,111 @@
+mod post_features;
 mod profile;
+mod rich_text;
 
+pub use post_features::{ExternalLink, PostFeatures, ReplyTo};
 pub use profile::ProfileDetails;
+pub use rich_text::{detect_facets, TextFacet, TextFacetKind};
 
+use atrium_api::types::{BlobRef, TypedBlobRef};
+use ipld_core::cid::multihash::Multihash;
+use ipld_core::cid::Cid;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct FollowRecord {
     pub subject: String,
 }
 
+/// An item of a list, along with the URI of the item record itself
+#[derive(Debug, Clone)]
+pub struct ListItem {
+    pub uri: String,
+    pub list: String,
+    pub subject: String,
+}
+
+/// Everything about a feed that's shown in Bluesky, as published in its feed generator record
+#[derive(Debug, Clone)]
+pub struct FeedGenerator {
+    pub display_name: String,
+    /// Plain text, with links, mentions and hashtags detected when published
+    pub description: Option<String>,
+    pub avatar: Option<BlobRef>,
+    pub content_mode: ContentMode,
+    /// Whether the feed wants to hear about interactions with its posts
+    pub accepts_interactions: bool,
+}
+
+impl FeedGenerator {
+    /// CID of the avatar, which is the same as the one of the file it was uploaded from.
+    pub fn avatar_cid(&self) -> Option<String> {
+        self.avatar.as_ref().map(|avatar| match avatar {
+            BlobRef::Typed(TypedBlobRef::Blob(blob)) => blob.r#ref.0.to_string(),
+            BlobRef::Untyped(blob) => blob.cid.clone(),
+        })
+    }
+}
+
+/// A feed generator record found in a repo
+#[derive(Debug, Clone)]
+pub struct PublishedFeedGenerator {
+    /// Record key, which is the short name of the feed
+    pub name: String,
+    pub uri: String,
+    pub cid: String,
+    /// DID of the service that serves the feed
+    pub did: String,
+    pub feed: FeedGenerator,
+}
+
+/// CID that a blob with the given contents gets when it's uploaded.
+pub fn blob_cid(bytes: &[u8]) -> String {
+    const RAW_CODEC: u64 = 0x55;
+    const SHA2_256_CODE: u64 = 0x12;
+
+    let digest = Multihash::wrap(SHA2_256_CODE, &Sha256::digest(bytes))
+        .expect("a SHA-256 digest should always fit into a multihash");
+
+    Cid::new_v1(RAW_CODEC, digest).to_string()
+}
+
+/// Kind of content that a feed serves, letting clients pick the right way to show it
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentMode {
+    #[default]
+    Unspecified,
+    Video,
+}
+
+impl ContentMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentMode::Unspecified => "app.bsky.feed.defs#contentModeUnspecified",
+            ContentMode::Video => "app.bsky.feed.defs#contentModeVideo",
+        }
+    }
+
+    /// Reads the content mode of a record, treating unknown ones as unspecified.
+    pub fn from_record(value: Option<&str>) -> Self {
+        match value {
+            Some(value) if value == ContentMode::Video.as_str() => ContentMode::Video,
+            _ => ContentMode::Unspecified,
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct LikeRecord {
     pub subject: Subject,
 }
 
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RepostRecord {
+    pub subject: Subject,
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Subject {
     pub cid: String,
@@ -38,3 +130,16 @@ pub struct Ref {
     pub cid: String,
     pub uri: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn computes_blob_cids() {
+        assert_eq!(
+            blob_cid(b""),
+            "bafkreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku"
+        );
+    }
+}
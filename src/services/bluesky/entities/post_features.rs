@@ -0,0 +1,274 @@
+use atrium_api::app::bsky::embed::record_with_media::MainMediaRefs;
+use atrium_api::app::bsky::embed::{external, images, video};
+use atrium_api::app::bsky::feed::post::{
+    RecordData as PostRecordData, RecordEmbedRefs, RecordLabelsRefs,
+};
+use atrium_api::app::bsky::richtext::facet::MainFeaturesItem;
+use atrium_api::types::Union;
+
+/// Everything about a post that algos might want to classify it on,
+/// pulled out of the embeds, facets and reply refs of the record.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PostFeatures {
+    pub text: String,
+    /// Language tags declared by the author, as is
+    pub langs: Vec<String>,
+    /// Alt texts of attached images and videos
+    pub alt_texts: Vec<String>,
+    pub external_link: Option<ExternalLink>,
+    pub quoted_post_uri: Option<String>,
+    pub reply: Option<ReplyTo>,
+    /// DIDs of mentioned accounts
+    pub mentions: Vec<String>,
+    pub links: Vec<String>,
+    /// Hashtags from both the facets and the record itself, without the `#`
+    pub tags: Vec<String>,
+    /// Labels that the author put on the post, e.g. `nudity`
+    pub self_labels: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExternalLink {
+    pub uri: String,
+    pub title: String,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplyTo {
+    pub root_uri: String,
+    pub parent_uri: String,
+}
+
+impl PostFeatures {
+    pub fn is_reply(&self) -> bool {
+        self.reply.is_some()
+    }
+
+    /// Text written by the author themselves: the post and the alt texts.
+    pub fn authored_text(&self) -> String {
+        std::iter::once(self.text.as_str())
+            .chain(self.alt_texts.iter().map(String::as_str))
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// All the text of the post, including the preview of the linked page.
+    pub fn all_text(&self) -> String {
+        let mut text = self.authored_text();
+
+        if let Some(link) = &self.external_link {
+            for part in [&link.title, &link.description] {
+                if !part.is_empty() {
+                    if !text.is_empty() {
+                        text.push('\n');
+                    }
+                    text.push_str(part);
+                }
+            }
+        }
+
+        text
+    }
+
+    fn add_images(&mut self, images: &images::MainData) {
+        self.alt_texts
+            .extend(images.images.iter().map(|image| image.alt.clone()));
+    }
+
+    fn add_video(&mut self, video: &video::MainData) {
+        self.alt_texts.extend(video.alt.clone());
+    }
+
+    fn add_external(&mut self, external: &external::MainData) {
+        self.external_link = Some(ExternalLink {
+            uri: external.external.uri.clone(),
+            title: external.external.title.clone(),
+            description: external.external.description.clone(),
+        });
+    }
+}
+
+impl From<&PostRecordData> for PostFeatures {
+    fn from(post: &PostRecordData) -> Self {
+        let mut features = PostFeatures {
+            text: post.text.clone(),
+            langs: post
+                .langs
+                .iter()
+                .flatten()
+                .map(|lang| lang.as_ref().as_str().to_owned())
+                .collect(),
+            reply: post.reply.as_ref().map(|reply| ReplyTo {
+                root_uri: reply.root.uri.clone(),
+                parent_uri: reply.parent.uri.clone(),
+            }),
+            tags: post.tags.clone().unwrap_or_default(),
+            self_labels: match &post.labels {
+                Some(Union::Refs(RecordLabelsRefs::ComAtprotoLabelDefsSelfLabels(labels))) => {
+                    labels
+                        .values
+                        .iter()
+                        .map(|label| label.val.clone())
+                        .collect()
+                }
+                Some(Union::Unknown(_)) | None => Vec::new(),
+            },
+            ..Default::default()
+        };
+
+        match &post.embed {
+            Some(Union::Refs(RecordEmbedRefs::AppBskyEmbedImagesMain(images))) => {
+                features.add_images(images);
+            }
+            Some(Union::Refs(RecordEmbedRefs::AppBskyEmbedVideoMain(video))) => {
+                features.add_video(video);
+            }
+            Some(Union::Refs(RecordEmbedRefs::AppBskyEmbedExternalMain(external))) => {
+                features.add_external(external);
+            }
+            Some(Union::Refs(RecordEmbedRefs::AppBskyEmbedRecordMain(record))) => {
+                features.quoted_post_uri = Some(record.record.uri.clone());
+            }
+            Some(Union::Refs(RecordEmbedRefs::AppBskyEmbedRecordWithMediaMain(embed))) => {
+                features.quoted_post_uri = Some(embed.record.record.uri.clone());
+
+                match &embed.media {
+                    Union::Refs(MainMediaRefs::AppBskyEmbedImagesMain(images)) => {
+                        features.add_images(images);
+                    }
+                    Union::Refs(MainMediaRefs::AppBskyEmbedVideoMain(video)) => {
+                        features.add_video(video);
+                    }
+                    Union::Refs(MainMediaRefs::AppBskyEmbedExternalMain(external)) => {
+                        features.add_external(external);
+                    }
+                    Union::Unknown(_) => {}
+                }
+            }
+            Some(Union::Unknown(_)) | None => {}
+        }
+
+        for facet in post.facets.iter().flatten() {
+            for feature in &facet.features {
+                match feature {
+                    Union::Refs(MainFeaturesItem::Mention(mention)) => {
+                        features.mentions.push(mention.did.as_str().to_owned());
+                    }
+                    Union::Refs(MainFeaturesItem::Link(link)) => {
+                        features.links.push(link.uri.clone());
+                    }
+                    Union::Refs(MainFeaturesItem::Tag(tag)) => {
+                        features.tags.push(tag.tag.clone());
+                    }
+                    Union::Unknown(_) => {}
+                }
+            }
+        }
+
+        features
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_post(json: &str) -> PostRecordData {
+        serde_json::from_str(json).expect("failed to parse post")
+    }
+
+    #[test]
+    fn extracts_everything_from_a_quote_reply_with_images() {
+        let post = parse_post(
+            r##"{
+                "$type": "app.bsky.feed.post",
+                "createdAt": "2024-10-20T12:00:00.000Z",
+                "text": "Hi @alice.test, see #amsterdam",
+                "langs": ["ru"],
+                "tags": ["expat"],
+                "labels": {
+                    "$type": "com.atproto.label.defs#selfLabels",
+                    "values": [{"val": "graphic-media"}]
+                },
+                "reply": {
+                    "root": {"uri": "at://did:plc:a/app.bsky.feed.post/1", "cid": "bafyreie5737gdxlw5i64vzichcalba3z2v5n6icifvx5xytvske7mr3hpm"},
+                    "parent": {"uri": "at://did:plc:a/app.bsky.feed.post/2", "cid": "bafyreie5737gdxlw5i64vzichcalba3z2v5n6icifvx5xytvske7mr3hpm"}
+                },
+                "facets": [
+                    {
+                        "index": {"byteStart": 3, "byteEnd": 14},
+                        "features": [{"$type": "app.bsky.richtext.facet#mention", "did": "did:plc:alice"}]
+                    },
+                    {
+                        "index": {"byteStart": 20, "byteEnd": 30},
+                        "features": [{"$type": "app.bsky.richtext.facet#tag", "tag": "amsterdam"}]
+                    }
+                ],
+                "embed": {
+                    "$type": "app.bsky.embed.recordWithMedia",
+                    "record": {
+                        "$type": "app.bsky.embed.record",
+                        "record": {"uri": "at://did:plc:b/app.bsky.feed.post/3", "cid": "bafyreie5737gdxlw5i64vzichcalba3z2v5n6icifvx5xytvske7mr3hpm"}
+                    },
+                    "media": {
+                        "$type": "app.bsky.embed.images",
+                        "images": [{
+                            "alt": "Каналы Амстердама",
+                            "image": {"$type": "blob", "ref": {"$link": "bafkreie5737gdxlw5i64vzichcalba3z2v5n6icifvx5xytvske7mr3hpm"}, "mimeType": "image/jpeg", "size": 1}
+                        }]
+                    }
+                }
+            }"##,
+        );
+
+        let features = PostFeatures::from(&post);
+
+        assert_eq!(features.langs, vec!["ru"]);
+        assert_eq!(features.alt_texts, vec!["Каналы Амстердама"]);
+        assert_eq!(
+            features.quoted_post_uri.as_deref(),
+            Some("at://did:plc:b/app.bsky.feed.post/3")
+        );
+        assert_eq!(
+            features.reply,
+            Some(ReplyTo {
+                root_uri: "at://did:plc:a/app.bsky.feed.post/1".to_owned(),
+                parent_uri: "at://did:plc:a/app.bsky.feed.post/2".to_owned(),
+            })
+        );
+        assert_eq!(features.mentions, vec!["did:plc:alice"]);
+        assert_eq!(features.tags, vec!["expat", "amsterdam"]);
+        assert_eq!(features.self_labels, vec!["graphic-media"]);
+        assert_eq!(
+            features.authored_text(),
+            "Hi @alice.test, see #amsterdam\nКаналы Амстердама"
+        );
+    }
+
+    #[test]
+    fn extracts_external_links() {
+        let post = parse_post(
+            r#"{
+                "createdAt": "2024-10-20T12:00:00.000Z",
+                "text": "",
+                "facets": [{
+                    "index": {"byteStart": 0, "byteEnd": 0},
+                    "features": [{"$type": "app.bsky.richtext.facet#link", "uri": "https://example.com/"}]
+                }],
+                "embed": {
+                    "$type": "app.bsky.embed.external",
+                    "external": {"uri": "https://example.com/", "title": "Example", "description": "An example"}
+                }
+            }"#,
+        );
+
+        let features = PostFeatures::from(&post);
+
+        assert!(!features.is_reply());
+        assert_eq!(features.links, vec!["https://example.com/"]);
+        assert_eq!(features.authored_text(), "");
+        assert_eq!(features.all_text(), "Example\nAn example");
+    }
+}
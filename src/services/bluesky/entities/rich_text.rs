@@ -0,0 +1,99 @@
+/// A part of a text that Bluesky should show as a link, a mention or a hashtag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextFacet {
+    /// Byte offset of the start of the part in the text, inclusive
+    pub start: usize,
+    /// Byte offset of the end of the part in the text, exclusive
+    pub end: usize,
+    pub kind: TextFacetKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextFacetKind {
+    Link(String),
+    /// Handle of the mentioned account, without the `@`
+    Mention(String),
+    /// Hashtag without the `#`
+    Tag(String),
+}
+
+/// Finds links, mentions and hashtags in plain text, the way Bluesky clients do
+/// when a post is written.
+pub fn detect_facets(text: &str) -> Vec<TextFacet> {
+    let mut facets = Vec::new();
+    let mut word_start = None;
+
+    for (position, c) in text.char_indices().chain([(text.len(), ' ')]) {
+        match (c.is_whitespace(), word_start) {
+            (true, Some(start)) => {
+                facets.extend(detect_facet(&text[start..position], start));
+                word_start = None;
+            }
+            (false, None) => word_start = Some(position),
+            _ => {}
+        }
+    }
+
+    facets
+}
+
+fn detect_facet(word: &str, start: usize) -> Option<TextFacet> {
+    // Punctuation right after a link or a tag most likely belongs to the sentence
+    let word = word.trim_end_matches(['.', ',', ';', ':', '!', '?', ')', '"', '\'']);
+    let end = start + word.len();
+
+    let kind = if word.starts_with("https://") || word.starts_with("http://") {
+        TextFacetKind::Link(word.to_owned())
+    } else if let Some(handle) = word.strip_prefix('@') {
+        if !handle.contains('.') || handle.starts_with('.') || handle.ends_with('.') {
+            return None;
+        }
+        TextFacetKind::Mention(handle.to_owned())
+    } else if let Some(tag) = word.strip_prefix('#') {
+        if tag.is_empty() || tag.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        TextFacetKind::Tag(tag.to_owned())
+    } else {
+        return None;
+    };
+
+    Some(TextFacet { start, end, kind })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_links_mentions_and_tags() {
+        let text = "Посты от @alice.bsky.social, см. https://example.com/about. #нидерланды #1";
+
+        let facets = detect_facets(text);
+
+        assert_eq!(
+            facets
+                .iter()
+                .map(|facet| &text[facet.start..facet.end])
+                .collect::<Vec<_>>(),
+            [
+                "@alice.bsky.social",
+                "https://example.com/about",
+                "#нидерланды"
+            ]
+        );
+        assert_eq!(
+            facets.iter().map(|facet| &facet.kind).collect::<Vec<_>>(),
+            [
+                &TextFacetKind::Mention("alice.bsky.social".to_owned()),
+                &TextFacetKind::Link("https://example.com/about".to_owned()),
+                &TextFacetKind::Tag("нидерланды".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_lone_symbols() {
+        assert!(detect_facets("@ # @someone #123 email@example.com").is_empty());
+    }
+}
@@ -1,6 +1,7 @@
 use std::{collections::HashMap, time::Duration};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
+use atrium_api::com::atproto::label::subscribe_labels::LabelsData;
 use atrium_api::{com::atproto::sync::subscribe_repos::Commit, types::string::Did};
 use chrono::{DateTime, Utc};
 use ipld_core::cid::Cid;
@@ -10,9 +11,10 @@ use tokio_tungstenite::{connect_async, tungstenite};
 use super::internals::ipld::Frame;
 
 const ACTION_CREATE: &str = "create";
+const ACTION_UPDATE: &str = "update";
 const ACTION_DELETE: &str = "delete";
 
-pub const FIREHOSE_HOST: &'static str = "wss://bsky.network";
+pub const FIREHOSE_HOST: &str = "wss://bsky.network";
 pub const STREAMING_TIMEOUT: Duration = Duration::from_secs(60);
 
 #[derive(Debug, Clone)]
@@ -27,16 +29,43 @@ pub enum Operation {
     Create {
         collection: String,
         did: Did,
+        rkey: String,
         cid: Cid,
         block: Vec<u8>,
     },
-    Delete {
+    Update {
         collection: String,
         did: Did,
+        rkey: String,
         cid: Cid,
+        block: Vec<u8>,
+    },
+    Delete {
+        collection: String,
+        did: Did,
+        rkey: String,
     },
 }
 
+/// A batch of labels that a labeler applied or took back.
+#[derive(Debug, Clone)]
+pub struct LabelsDetails {
+    pub seq: i64,
+    pub labels: Vec<LabelDetails>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LabelDetails {
+    /// DID of the labeler
+    pub src: String,
+    /// AT-URI of the labeled record, or DID of the labeled account
+    pub uri: String,
+    pub val: String,
+    /// Whether the label is taken back rather than applied
+    pub negated: bool,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
 /// Subscribe to the bluesky firehose.
 pub async fn subscribe_to_operations(
     cursor: Option<i64>,
@@ -55,6 +84,50 @@ pub async fn subscribe_to_operations(
     Ok(stream)
 }
 
+/// Subscribe to the labels of a labeler, e.g. `wss://mod.bsky.app`.
+pub async fn subscribe_to_labels(
+    labeler_url: &str,
+    cursor: Option<i64>,
+) -> Result<impl Stream<Item = Result<tungstenite::Message, tungstenite::Error>>> {
+    let url = match cursor {
+        Some(cursor) => format!(
+            "{}/xrpc/com.atproto.label.subscribeLabels?cursor={}",
+            labeler_url, cursor
+        ),
+        None => format!("{}/xrpc/com.atproto.label.subscribeLabels", labeler_url),
+    };
+
+    let (stream, _) = connect_async(url).await?;
+    let stream = Box::pin(stream);
+
+    Ok(stream)
+}
+
+pub fn handle_label_message(message: &[u8]) -> Result<Option<LabelsDetails>> {
+    let labels = match Frame::try_from(message)? {
+        Frame::Message(Some(t), message) if t == "#labels" => {
+            serde_ipld_dagcbor::from_slice::<LabelsData>(&message.body)?
+        }
+        Frame::Message(_, _) => return Ok(None),
+        Frame::Error(err) => bail!("Frame error: {err:?}"),
+    };
+
+    Ok(Some(LabelsDetails {
+        seq: labels.seq,
+        labels: labels
+            .labels
+            .into_iter()
+            .map(|label| LabelDetails {
+                src: label.src.to_string(),
+                uri: label.uri.clone(),
+                val: label.val.clone(),
+                negated: label.neg.unwrap_or(false),
+                expires_at: label.exp.as_ref().map(|exp| (*exp.as_ref()).into()),
+            })
+            .collect(),
+    }))
+}
+
 pub async fn handle_message(message: &[u8]) -> Result<Option<CommitDetails>> {
     let commit = match parse_commit_from_message(message)? {
         Some(commit) => commit,
@@ -96,11 +169,14 @@ async fn extract_operations(commit: &Commit) -> Result<Vec<Operation>> {
         .collect();
 
     for op in &commit.ops {
-        let collection = op.path.split('/').next().expect("op.path is empty");
+        let (collection, rkey) = match op.path.split_once('/') {
+            Some(parts) => parts,
+            None => continue,
+        };
         let action = op.action.as_str();
 
         let operation = match action {
-            ACTION_CREATE => {
+            ACTION_CREATE | ACTION_UPDATE => {
                 let cid = match &op.cid {
                     Some(cid_link) => cid_link.0,
                     None => continue,
@@ -111,25 +187,30 @@ async fn extract_operations(commit: &Commit) -> Result<Vec<Operation>> {
                     None => continue,
                 };
 
-                Operation::Create {
-                    collection: collection.to_string(),
-                    did: commit.repo.clone(),
-                    cid: cid,
-                    block: block.to_vec(),
-                }
-            }
-            ACTION_DELETE => {
-                let cid = match &op.cid {
-                    Some(cid_link) => cid_link.0,
-                    None => continue,
-                };
-
-                Operation::Delete {
-                    collection: collection.to_string(),
-                    did: commit.repo.clone(),
-                    cid: cid,
+                if action == ACTION_CREATE {
+                    Operation::Create {
+                        collection: collection.to_string(),
+                        did: commit.repo.clone(),
+                        rkey: rkey.to_string(),
+                        cid,
+                        block: block.to_vec(),
+                    }
+                } else {
+                    Operation::Update {
+                        collection: collection.to_string(),
+                        did: commit.repo.clone(),
+                        rkey: rkey.to_string(),
+                        cid,
+                        block: block.to_vec(),
+                    }
                 }
             }
+            // Deletes carry no CID, only the path of the removed record
+            ACTION_DELETE => Operation::Delete {
+                collection: collection.to_string(),
+                did: commit.repo.clone(),
+                rkey: rkey.to_string(),
+            },
             _ => continue,
         };
 
@@ -1,16 +1,235 @@
 use std::ops::Deref;
+use std::time::Duration;
 
 use anyhow::Result;
 use chrono::{DateTime, Utc};
-use sqlx::postgres::{PgPool, PgPoolOptions};
+use serde::Serialize;
+use sqlx::postgres::{PgConnection, PgPool, PgPoolOptions};
+use sqlx::Connection;
+
+#[cfg(test)]
+pub mod testing;
 
 pub struct Post {
     pub indexed_at: DateTime<Utc>,
+    /// Point in time that the post is ordered by, see [`Ranking`]
+    pub ranked_at: DateTime<Utc>,
     pub author_did: String,
     pub cid: String,
     pub uri: String,
 }
 
+/// Order in which posts of a feed are served.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Ranking {
+    /// Newest posts first
+    Chronological,
+    /// Posts are ranked as if they were indexed later the more likes and reposts they get:
+    /// every doubling of engagement, with a repost counting as two likes, is worth as much
+    /// as being `gravity` fresher.
+    ///
+    /// The rank of a post only changes when its engagement does, not as time passes,
+    /// so it's stored along with the post. Pages are cut off at the rank that the last
+    /// one ended at though, so a post whose engagement changes while someone pages
+    /// through the feed can end up on either side of that and be skipped or repeated.
+    Hot { gravity: Duration },
+}
+
+impl Ranking {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Ranking::Chronological => "chronological",
+            Ranking::Hot { .. } => "hot",
+        }
+    }
+
+    fn gravity_secs(&self) -> f64 {
+        match self {
+            Ranking::Chronological => 0.0,
+            Ranking::Hot { gravity } => gravity.as_secs_f64(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EngagementKind {
+    Like,
+    Repost,
+}
+
+impl EngagementKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            EngagementKind::Like => "like",
+            EngagementKind::Repost => "repost",
+        }
+    }
+}
+
+/// A like or a repost of a post.
+pub struct Engagement {
+    pub uri: String,
+    pub post_uri: String,
+    pub kind: EngagementKind,
+}
+
+/// A like or a repost that's been counted towards the ranking of a post.
+pub struct CountedEngagement {
+    pub uri: String,
+    pub post_uri: String,
+}
+
+pub struct Profile {
+    pub did: String,
+    pub display_name: Option<String>,
+    pub description: Option<String>,
+}
+
+/// What's known about how long an author has been around.
+pub struct ProfileAge {
+    pub first_seen_at: Option<DateTime<Utc>>,
+    /// When the account was created, once it's been looked up
+    pub account_created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct ProfileCountryOverride {
+    pub did: String,
+    pub country: String,
+    pub set_by: String,
+    pub set_at: DateTime<Utc>,
+}
+
+/// A profile along with the countries it's considered to be living in.
+#[derive(Serialize)]
+pub struct ProfileCountry {
+    pub did: String,
+    pub display_name: Option<String>,
+    pub first_seen_at: Option<DateTime<Utc>>,
+    pub has_been_processed: bool,
+    pub likely_country_of_living: Option<String>,
+    /// Country set manually, which takes precedence over the inferred one
+    pub country_override: Option<String>,
+}
+
+/// Counts of what has been indexed so far.
+#[derive(Serialize)]
+pub struct IndexingStats {
+    pub posts: i64,
+    pub posts_last_day: i64,
+    pub latest_indexed_at: Option<DateTime<Utc>>,
+    pub profiles: i64,
+    /// Profiles whose country of living is yet to be inferred
+    pub unprocessed_profiles: i64,
+    pub feeds: Vec<FeedStats>,
+}
+
+#[derive(Serialize)]
+pub struct FeedStats {
+    pub feed: String,
+    pub posts: i64,
+    pub posts_last_day: i64,
+}
+
+pub struct ListItem {
+    pub uri: String,
+    pub list_uri: String,
+    pub subject_did: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModerationActionKind {
+    Ban,
+    Unban,
+    Hide,
+    Unhide,
+}
+
+impl ModerationActionKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ModerationActionKind::Ban => "ban",
+            ModerationActionKind::Unban => "unban",
+            ModerationActionKind::Hide => "hide",
+            ModerationActionKind::Unhide => "unhide",
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct BannedAuthor {
+    pub did: String,
+    pub reason: String,
+    pub banned_by: String,
+    pub banned_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct HiddenPost {
+    pub uri: String,
+    pub reason: String,
+    pub hidden_by: String,
+    pub hidden_at: DateTime<Utc>,
+}
+
+/// An entry of the audit log of moderation.
+#[derive(Serialize)]
+pub struct ModerationAction {
+    pub action: String,
+    /// DID of the author or AT-URI of the post that was acted upon
+    pub subject: String,
+    pub reason: Option<String>,
+    pub performed_by: String,
+    pub performed_at: DateTime<Utc>,
+}
+
+/// A post that was kept out of every feed for looking like spam.
+#[derive(Serialize)]
+pub struct SpamDecision {
+    pub did: String,
+    pub uri: String,
+    pub reason: String,
+    pub details: String,
+    pub decided_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct PinnedPost {
+    pub feed: String,
+    pub uri: String,
+    pub pinned_by: String,
+    pub pinned_at: DateTime<Utc>,
+}
+
+/// Which posts to leave out of a feed, pinned ones included.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FeedFilter<'a> {
+    /// Only posts by authors living in one of these countries are kept. A manual
+    /// country override of the author, if any, takes precedence over the inferred one.
+    pub countries: Option<&'a [String]>,
+    /// Posts that a labeler put any of these labels on, or on their authors
+    pub excluded_labels: &'a [String],
+    /// Posts that this viewer asked to see less of
+    pub viewer_did: Option<&'a str>,
+    /// Only posts by authors who are currently members of this list are kept, so that
+    /// posts of members who were taken off the list go with them
+    pub list_uri: Option<&'a str>,
+    /// Posts that are shown elsewhere in the feed, such as pinned ones
+    pub excluded_uris: &'a [String],
+}
+
+/// Feedback that a viewer gave on a post they were served in one of our feeds.
+pub struct Interaction {
+    pub post_uri: String,
+    /// Reference to the kind of interaction, e.g. `app.bsky.feed.defs#requestLess`
+    pub event: String,
+    /// Context we attached to the post when serving it, passed back as is
+    pub feed_context: Option<String>,
+}
+
+/// Interaction event of a viewer asking to see fewer posts like this one.
+const REQUEST_LESS: &str = atrium_api::app::bsky::feed::defs::REQUEST_LESS;
+
 pub struct Database {
     connection_pool: PgPool,
 }
@@ -30,13 +249,49 @@ impl Database {
         })
     }
 
-    pub async fn insert_post(&self, _author_did: &str, cid: &str, uri: &str) -> Result<()> {
-        Ok(
-            sqlx::query!("INSERT INTO Post (cid, uri) VALUES ($1, $2)", cid, uri)
-                .execute(&self.connection_pool)
-                .await
-                .map(|_| ())?,
+    /// Checks that the database can be reached.
+    pub async fn ping(&self) -> Result<()> {
+        Ok(self.connection_pool.acquire().await?.ping().await?)
+    }
+
+    /// Stores a post along with the feeds it was accepted into.
+    pub async fn insert_post(
+        &self,
+        author_did: &str,
+        cid: &str,
+        uri: &str,
+        feeds: &[&str],
+    ) -> Result<()> {
+        let mut transaction = self.connection_pool.begin().await?;
+
+        sqlx::query!(
+            "INSERT INTO Post (author_did, cid, uri) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+            author_did,
+            cid,
+            uri
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        for feed in feeds {
+            sqlx::query!(
+                "INSERT INTO FeedPost (feed, uri, cid) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+                feed,
+                uri,
+                cid
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+
+        sqlx::query!(
+            "INSERT INTO Profile (did) VALUES ($1) ON CONFLICT (did) DO NOTHING",
+            author_did
         )
+        .execute(&mut *transaction)
+        .await?;
+
+        Ok(transaction.commit().await?)
     }
 
     pub async fn delete_post(&self, uri: &str) -> Result<bool> {
@@ -46,6 +301,866 @@ impl Database {
             .map(|result| result.rows_affected() > 0)?)
     }
 
+    /// Fetches URIs of the most recently indexed posts, oldest first, along with
+    /// the likes and reposts of them that have been counted.
+    pub async fn fetch_recent_engagement(
+        &self,
+        limit: usize,
+    ) -> Result<(Vec<String>, Vec<CountedEngagement>)> {
+        let mut post_uris: Vec<String> = sqlx::query_scalar!(
+            r#"
+            SELECT uri AS "uri!" FROM Post
+            WHERE uri IS NOT NULL
+            ORDER BY indexed_at DESC, id DESC
+            LIMIT $1
+            "#,
+            limit as i64
+        )
+        .fetch_all(&self.connection_pool)
+        .await?;
+
+        post_uris.reverse();
+
+        let engagements = sqlx::query_as!(
+            CountedEngagement,
+            "SELECT uri, post_uri FROM PostEngagement WHERE post_uri = ANY($1)",
+            &post_uris
+        )
+        .fetch_all(&self.connection_pool)
+        .await?;
+
+        Ok((post_uris, engagements))
+    }
+
+    /// Counts likes and reposts of posts, and stops counting the ones that were
+    /// taken back, all at once, ranking the posts anew in feeds with the given rankings.
+    pub async fn update_engagement(
+        &self,
+        added: &[Engagement],
+        removed: &[String],
+        rankings: &[(&str, Ranking)],
+    ) -> Result<()> {
+        let mut transaction = self.connection_pool.begin().await?;
+        let mut updated_uris = Vec::new();
+
+        if !added.is_empty() {
+            let (uris, (post_uris, kinds)): (Vec<&str>, (Vec<&str>, Vec<&str>)) = added
+                .iter()
+                .map(|e| (e.uri.as_str(), (e.post_uri.as_str(), e.kind.as_str())))
+                .unzip();
+
+            updated_uris.extend(
+                sqlx::query_scalar!(
+                    r#"
+                WITH Inserted AS (
+                    INSERT INTO PostEngagement (uri, post_uri, kind)
+                    SELECT Added.uri, Added.post_uri, Added.kind
+                    FROM UNNEST($1::TEXT[], $2::TEXT[], $3::TEXT[]) AS Added (uri, post_uri, kind)
+                    WHERE EXISTS (SELECT 1 FROM Post WHERE uri = Added.post_uri)
+                    ON CONFLICT DO NOTHING
+                    RETURNING post_uri, kind
+                ), Counts AS (
+                    SELECT
+                        post_uri,
+                        COUNT(*) FILTER (WHERE kind = 'like') AS likes,
+                        COUNT(*) FILTER (WHERE kind = 'repost') AS reposts
+                    FROM Inserted
+                    GROUP BY post_uri
+                )
+                UPDATE Post SET
+                    like_count = like_count + Counts.likes::INT,
+                    repost_count = repost_count + Counts.reposts::INT
+                FROM Counts
+                WHERE Post.uri = Counts.post_uri
+                RETURNING Post.uri AS "uri!"
+                "#,
+                    &uris as &[&str],
+                    &post_uris as &[&str],
+                    &kinds as &[&str]
+                )
+                .fetch_all(&mut *transaction)
+                .await?,
+            );
+        }
+
+        if !removed.is_empty() {
+            updated_uris.extend(
+                sqlx::query_scalar!(
+                    r#"
+                WITH Deleted AS (
+                    DELETE FROM PostEngagement WHERE uri = ANY($1) RETURNING post_uri, kind
+                ), Counts AS (
+                    SELECT
+                        post_uri,
+                        COUNT(*) FILTER (WHERE kind = 'like') AS likes,
+                        COUNT(*) FILTER (WHERE kind = 'repost') AS reposts
+                    FROM Deleted
+                    GROUP BY post_uri
+                )
+                UPDATE Post SET
+                    like_count = like_count - Counts.likes::INT,
+                    repost_count = repost_count - Counts.reposts::INT
+                FROM Counts
+                WHERE Post.uri = Counts.post_uri
+                RETURNING Post.uri AS "uri!"
+                "#,
+                    removed
+                )
+                .fetch_all(&mut *transaction)
+                .await?,
+            );
+        }
+
+        rank_feed_posts(&mut transaction, rankings, Some(&updated_uris)).await?;
+
+        Ok(transaction.commit().await?)
+    }
+
+    /// Ranks posts with any engagement anew, for when the way that the feeds are
+    /// ranked might have changed.
+    pub async fn rank_feed_posts(&self, rankings: &[(&str, Ranking)]) -> Result<()> {
+        let mut connection = self.connection_pool.acquire().await?;
+
+        rank_feed_posts(&mut connection, rankings, None).await
+    }
+
+    /// Fetches posts of the given feed in the order of their stored rank, leaving out the
+    /// ones of banned authors, hidden posts and whatever else the filter says.
+    pub async fn fetch_feed_posts(
+        &self,
+        feed: &str,
+        filter: &FeedFilter<'_>,
+        limit: usize,
+        earlier_than: Option<(DateTime<Utc>, &str)>,
+    ) -> Result<Vec<Post>> {
+        let (earlier_than_date, earlier_than_cid) = earlier_than.unzip();
+
+        Ok(sqlx::query_as!(
+            Post,
+            r#"
+            SELECT
+                Post.indexed_at AS "indexed_at!",
+                FeedPost.ranked_at AS "ranked_at!",
+                Post.author_did AS "author_did!",
+                FeedPost.cid AS "cid!",
+                Post.uri AS "uri!"
+            FROM FeedPost
+            INNER JOIN Post ON Post.uri = FeedPost.uri
+            LEFT JOIN Profile ON Profile.did = Post.author_did
+            LEFT JOIN ProfileCountryOverride ON ProfileCountryOverride.did = Post.author_did
+            WHERE
+                FeedPost.feed = $1
+                AND ($3::TIMESTAMPTZ IS NULL OR (FeedPost.ranked_at, FeedPost.cid) < ($3, $4))
+                AND (
+                    $2::TEXT[] IS NULL
+                    OR COALESCE(ProfileCountryOverride.country, Profile.likely_country_of_living) = ANY($2)
+                )
+                AND NOT EXISTS (SELECT 1 FROM BannedAuthor WHERE did = Post.author_did)
+                AND NOT EXISTS (SELECT 1 FROM HiddenPost WHERE uri = Post.uri)
+                AND NOT EXISTS (
+                    SELECT 1 FROM Label
+                    WHERE uri IN (Post.uri, Post.author_did)
+                        AND val = ANY($8)
+                        AND (expires_at IS NULL OR expires_at > NOW())
+                )
+                AND NOT EXISTS (
+                    SELECT 1 FROM Interaction
+                    WHERE viewer_did = $6 AND post_uri = Post.uri AND event = $7
+                )
+                AND (
+                    $9::TEXT IS NULL
+                    OR EXISTS (
+                        SELECT 1 FROM ListItem
+                        WHERE list_uri = $9 AND subject_did = Post.author_did
+                    )
+                )
+                AND Post.uri <> ALL($10)
+            ORDER BY FeedPost.ranked_at DESC, FeedPost.cid DESC
+            LIMIT $5
+            "#,
+            feed,
+            filter.countries,
+            earlier_than_date,
+            earlier_than_cid,
+            limit as i64,
+            filter.viewer_did,
+            REQUEST_LESS,
+            filter.excluded_labels,
+            filter.list_uri,
+            filter.excluded_uris
+        )
+        .fetch_all(&self.connection_pool)
+        .await?)
+    }
+
+    /// Stores interactions of a viewer with posts, only keeping the latest time that
+    /// they interacted with a post in the same way.
+    pub async fn insert_interactions(
+        &self,
+        viewer_did: &str,
+        interactions: &[Interaction],
+    ) -> Result<()> {
+        let mut transaction = self.connection_pool.begin().await?;
+
+        for interaction in interactions {
+            sqlx::query!(
+                r#"
+                INSERT INTO Interaction (viewer_did, post_uri, event, feed_context)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (viewer_did, post_uri, event) DO UPDATE SET
+                    feed_context = EXCLUDED.feed_context,
+                    interacted_at = NOW()
+                "#,
+                viewer_did,
+                interaction.post_uri,
+                interaction.event,
+                interaction.feed_context
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+
+        Ok(transaction.commit().await?)
+    }
+
+    /// Fetches posts pinned to the top of a feed, or of all feeds, most recently pinned first.
+    pub async fn fetch_pinned_posts(&self, feed: Option<&str>) -> Result<Vec<PinnedPost>> {
+        Ok(sqlx::query_as!(
+            PinnedPost,
+            r#"
+            SELECT feed, uri, pinned_by, pinned_at FROM PinnedPost
+            WHERE $1::TEXT IS NULL OR feed = $1
+            ORDER BY pinned_at DESC, id DESC
+            "#,
+            feed
+        )
+        .fetch_all(&self.connection_pool)
+        .await?)
+    }
+
+    /// Fetches URIs of posts pinned to the top of a feed, most recently pinned first,
+    /// leaving out the same posts as [`Database::fetch_feed_posts`] would.
+    ///
+    /// Pinned posts don't have to be in the feed, so their authors are taken from
+    /// their URIs.
+    pub async fn fetch_feed_pins(
+        &self,
+        feed: &str,
+        filter: &FeedFilter<'_>,
+        limit: usize,
+    ) -> Result<Vec<String>> {
+        Ok(sqlx::query_scalar!(
+            r#"
+            SELECT PinnedPost.uri FROM PinnedPost
+            LEFT JOIN Profile ON Profile.did = split_part(PinnedPost.uri, '/', 3)
+            LEFT JOIN ProfileCountryOverride
+                ON ProfileCountryOverride.did = split_part(PinnedPost.uri, '/', 3)
+            WHERE
+                PinnedPost.feed = $1
+                AND (
+                    $2::TEXT[] IS NULL
+                    OR COALESCE(ProfileCountryOverride.country, Profile.likely_country_of_living) = ANY($2)
+                )
+                AND NOT EXISTS (
+                    SELECT 1 FROM BannedAuthor WHERE did = split_part(PinnedPost.uri, '/', 3)
+                )
+                AND NOT EXISTS (SELECT 1 FROM HiddenPost WHERE uri = PinnedPost.uri)
+                AND NOT EXISTS (
+                    SELECT 1 FROM Label
+                    WHERE uri IN (PinnedPost.uri, split_part(PinnedPost.uri, '/', 3))
+                        AND val = ANY($3)
+                        AND (expires_at IS NULL OR expires_at > NOW())
+                )
+                AND NOT EXISTS (
+                    SELECT 1 FROM Interaction
+                    WHERE viewer_did = $4 AND post_uri = PinnedPost.uri AND event = $5
+                )
+                AND (
+                    $6::TEXT IS NULL
+                    OR EXISTS (
+                        SELECT 1 FROM ListItem
+                        WHERE list_uri = $6 AND subject_did = split_part(PinnedPost.uri, '/', 3)
+                    )
+                )
+                AND PinnedPost.uri <> ALL($7)
+            ORDER BY PinnedPost.pinned_at DESC, PinnedPost.id DESC
+            LIMIT $8
+            "#,
+            feed,
+            filter.countries,
+            filter.excluded_labels,
+            filter.viewer_did,
+            REQUEST_LESS,
+            filter.list_uri,
+            filter.excluded_uris,
+            limit as i64
+        )
+        .fetch_all(&self.connection_pool)
+        .await?)
+    }
+
+    pub async fn pin_post(&self, feed: &str, uri: &str, pinned_by: &str) -> Result<bool> {
+        Ok(sqlx::query!(
+            "INSERT INTO PinnedPost (feed, uri, pinned_by) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+            feed,
+            uri,
+            pinned_by
+        )
+        .execute(&self.connection_pool)
+        .await
+        .map(|result| result.rows_affected() > 0)?)
+    }
+
+    pub async fn unpin_post(&self, feed: &str, uri: &str) -> Result<bool> {
+        Ok(sqlx::query!(
+            "DELETE FROM PinnedPost WHERE feed = $1 AND uri = $2",
+            feed,
+            uri
+        )
+        .execute(&self.connection_pool)
+        .await
+        .map(|result| result.rows_affected() > 0)?)
+    }
+
+    /// Fetches up to `limit` profiles that still need their country of living inferred,
+    /// the ones that failed to be classified before last.
+    ///
+    /// Profiles with a manual country override are skipped, since whatever
+    /// gets inferred for them would never be used.
+    pub async fn fetch_unprocessed_profiles(&self, limit: usize) -> Result<Vec<Profile>> {
+        Ok(sqlx::query!(
+            r#"
+            SELECT did, display_name, description FROM Profile
+            WHERE has_been_processed = FALSE
+                AND did NOT IN (SELECT did FROM ProfileCountryOverride)
+            ORDER BY classification_failed_at ASC NULLS FIRST, id ASC
+            LIMIT $1
+            "#,
+            limit as i64
+        )
+        .map(|r| Profile {
+            did: r.did.unwrap_or_default(),
+            display_name: r.display_name,
+            description: r.description,
+        })
+        .fetch_all(&self.connection_pool)
+        .await?)
+    }
+
+    /// Makes a profile, or every profile, have its country of living inferred again.
+    ///
+    /// The country inferred before is kept until then, so that the posts of the
+    /// profiles don't drop out of the feeds in the meantime.
+    pub async fn reset_profile_classification(&self, did: Option<&str>) -> Result<u64> {
+        Ok(sqlx::query!(
+            r#"
+            UPDATE Profile SET has_been_processed = FALSE
+            WHERE has_been_processed = TRUE AND ($1::TEXT IS NULL OR did = $1)
+            "#,
+            did
+        )
+        .execute(&self.connection_pool)
+        .await
+        .map(|result| result.rows_affected())?)
+    }
+
+    /// Fetches the most recently seen profiles along with their countries, optionally
+    /// only those considered to be living in the given country.
+    pub async fn fetch_profile_countries(
+        &self,
+        country: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<ProfileCountry>> {
+        Ok(sqlx::query_as!(
+            ProfileCountry,
+            r#"
+            SELECT
+                Profile.did AS "did!",
+                Profile.display_name,
+                Profile.first_seen_at,
+                COALESCE(Profile.has_been_processed, FALSE) AS "has_been_processed!",
+                Profile.likely_country_of_living,
+                ProfileCountryOverride.country AS "country_override?"
+            FROM Profile
+            LEFT JOIN ProfileCountryOverride ON ProfileCountryOverride.did = Profile.did
+            WHERE Profile.did IS NOT NULL
+                AND (
+                    $1::TEXT IS NULL
+                    OR COALESCE(ProfileCountryOverride.country, Profile.likely_country_of_living) = $1
+                )
+            ORDER BY Profile.first_seen_at DESC, Profile.id DESC
+            LIMIT $2
+            "#,
+            country,
+            limit as i64
+        )
+        .fetch_all(&self.connection_pool)
+        .await?)
+    }
+
+    /// Stores fresh profile details for a known profile.
+    ///
+    /// If the details differ from what was stored before, the inferred country
+    /// is discarded so that the profile gets classified again.
+    pub async fn update_profile_details(
+        &self,
+        did: &str,
+        display_name: &str,
+        description: &str,
+    ) -> Result<bool> {
+        Ok(sqlx::query!(
+            r#"
+            UPDATE Profile SET
+                has_been_processed = has_been_processed
+                    AND display_name IS NOT DISTINCT FROM $2
+                    AND description IS NOT DISTINCT FROM $3,
+                likely_country_of_living = CASE
+                    WHEN display_name IS NOT DISTINCT FROM $2
+                        AND description IS NOT DISTINCT FROM $3
+                    THEN likely_country_of_living
+                END,
+                display_name = $2,
+                description = $3,
+                details_updated_at = NOW()
+            WHERE did = $1
+            "#,
+            did,
+            display_name,
+            description
+        )
+        .execute(&self.connection_pool)
+        .await
+        .map(|result| result.rows_affected() > 0)?)
+    }
+
+    /// When we first indexed a post of the author, if we ever did.
+    pub async fn fetch_profile_age(&self, did: &str) -> Result<Option<ProfileAge>> {
+        Ok(sqlx::query_as!(
+            ProfileAge,
+            "SELECT first_seen_at, account_created_at FROM Profile WHERE did = $1",
+            did
+        )
+        .fetch_optional(&self.connection_pool)
+        .await?)
+    }
+
+    pub async fn store_account_created_at(
+        &self,
+        did: &str,
+        account_created_at: DateTime<Utc>,
+    ) -> Result<bool> {
+        Ok(sqlx::query!(
+            "UPDATE Profile SET account_created_at = $2 WHERE did = $1",
+            did,
+            account_created_at
+        )
+        .execute(&self.connection_pool)
+        .await
+        .map(|result| result.rows_affected() > 0)?)
+    }
+
+    pub async fn mark_profile_classification_failed(&self, did: &str) -> Result<bool> {
+        Ok(sqlx::query!(
+            "UPDATE Profile SET classification_failed_at = NOW() WHERE did = $1",
+            did
+        )
+        .execute(&self.connection_pool)
+        .await
+        .map(|result| result.rows_affected() > 0)?)
+    }
+
+    pub async fn store_profile_country(&self, did: &str, country: &str) -> Result<bool> {
+        Ok(sqlx::query!(
+            "UPDATE Profile SET has_been_processed = TRUE, likely_country_of_living = $2 WHERE did = $1",
+            did,
+            country
+        )
+        .execute(&self.connection_pool)
+        .await
+        .map(|result| result.rows_affected() > 0)?)
+    }
+
+    pub async fn fetch_profile_country_overrides(&self) -> Result<Vec<ProfileCountryOverride>> {
+        Ok(sqlx::query_as!(
+            ProfileCountryOverride,
+            "SELECT did, country, set_by, set_at FROM ProfileCountryOverride ORDER BY set_at DESC"
+        )
+        .fetch_all(&self.connection_pool)
+        .await?)
+    }
+
+    pub async fn set_profile_country_override(
+        &self,
+        did: &str,
+        country: &str,
+        set_by: &str,
+    ) -> Result<()> {
+        Ok(sqlx::query!(
+            r#"
+            INSERT INTO ProfileCountryOverride (did, country, set_by) VALUES ($1, $2, $3)
+            ON CONFLICT (did) DO UPDATE
+                SET country = EXCLUDED.country, set_by = EXCLUDED.set_by, set_at = NOW()
+            "#,
+            did,
+            country,
+            set_by
+        )
+        .execute(&self.connection_pool)
+        .await
+        .map(|_| ())?)
+    }
+
+    pub async fn clear_profile_country_override(&self, did: &str) -> Result<bool> {
+        Ok(
+            sqlx::query!("DELETE FROM ProfileCountryOverride WHERE did = $1", did)
+                .execute(&self.connection_pool)
+                .await
+                .map(|result| result.rows_affected() > 0)?,
+        )
+    }
+
+    pub async fn is_author_banned(&self, did: &str) -> Result<bool> {
+        Ok(sqlx::query_scalar!(
+            r#"SELECT EXISTS (SELECT 1 FROM BannedAuthor WHERE did = $1) AS "exists!""#,
+            did
+        )
+        .fetch_one(&self.connection_pool)
+        .await?)
+    }
+
+    pub async fn fetch_banned_authors(&self) -> Result<Vec<BannedAuthor>> {
+        Ok(sqlx::query_as!(
+            BannedAuthor,
+            "SELECT did, reason, banned_by, banned_at FROM BannedAuthor ORDER BY banned_at DESC"
+        )
+        .fetch_all(&self.connection_pool)
+        .await?)
+    }
+
+    /// Bans an author from every feed, both their existing and their future posts.
+    pub async fn ban_author(&self, did: &str, reason: &str, banned_by: &str) -> Result<()> {
+        let mut transaction = self.connection_pool.begin().await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO BannedAuthor (did, reason, banned_by) VALUES ($1, $2, $3)
+            ON CONFLICT (did) DO UPDATE
+                SET reason = EXCLUDED.reason, banned_by = EXCLUDED.banned_by, banned_at = NOW()
+            "#,
+            did,
+            reason,
+            banned_by
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        log_moderation_action(
+            &mut transaction,
+            ModerationActionKind::Ban,
+            did,
+            Some(reason),
+            banned_by,
+        )
+        .await?;
+
+        Ok(transaction.commit().await?)
+    }
+
+    pub async fn unban_author(&self, did: &str, unbanned_by: &str) -> Result<bool> {
+        let mut transaction = self.connection_pool.begin().await?;
+
+        let was_banned = sqlx::query!("DELETE FROM BannedAuthor WHERE did = $1", did)
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected()
+            > 0;
+
+        if was_banned {
+            log_moderation_action(
+                &mut transaction,
+                ModerationActionKind::Unban,
+                did,
+                None,
+                unbanned_by,
+            )
+            .await?;
+        }
+
+        transaction.commit().await?;
+
+        Ok(was_banned)
+    }
+
+    pub async fn fetch_hidden_posts(&self) -> Result<Vec<HiddenPost>> {
+        Ok(sqlx::query_as!(
+            HiddenPost,
+            "SELECT uri, reason, hidden_by, hidden_at FROM HiddenPost ORDER BY hidden_at DESC"
+        )
+        .fetch_all(&self.connection_pool)
+        .await?)
+    }
+
+    /// Hides a post from every feed.
+    pub async fn hide_post(&self, uri: &str, reason: &str, hidden_by: &str) -> Result<()> {
+        let mut transaction = self.connection_pool.begin().await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO HiddenPost (uri, reason, hidden_by) VALUES ($1, $2, $3)
+            ON CONFLICT (uri) DO UPDATE
+                SET reason = EXCLUDED.reason, hidden_by = EXCLUDED.hidden_by, hidden_at = NOW()
+            "#,
+            uri,
+            reason,
+            hidden_by
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        log_moderation_action(
+            &mut transaction,
+            ModerationActionKind::Hide,
+            uri,
+            Some(reason),
+            hidden_by,
+        )
+        .await?;
+
+        Ok(transaction.commit().await?)
+    }
+
+    pub async fn unhide_post(&self, uri: &str, unhidden_by: &str) -> Result<bool> {
+        let mut transaction = self.connection_pool.begin().await?;
+
+        let was_hidden = sqlx::query!("DELETE FROM HiddenPost WHERE uri = $1", uri)
+            .execute(&mut *transaction)
+            .await?
+            .rows_affected()
+            > 0;
+
+        if was_hidden {
+            log_moderation_action(
+                &mut transaction,
+                ModerationActionKind::Unhide,
+                uri,
+                None,
+                unhidden_by,
+            )
+            .await?;
+        }
+
+        transaction.commit().await?;
+
+        Ok(was_hidden)
+    }
+
+    /// Fetches the audit log of moderation, most recent actions first.
+    pub async fn fetch_moderation_actions(&self, limit: usize) -> Result<Vec<ModerationAction>> {
+        Ok(sqlx::query_as!(
+            ModerationAction,
+            r#"
+            SELECT action, subject, reason, performed_by, performed_at FROM ModerationAction
+            ORDER BY performed_at DESC, id DESC
+            LIMIT $1
+            "#,
+            limit as i64
+        )
+        .fetch_all(&self.connection_pool)
+        .await?)
+    }
+
+    pub async fn record_spam_decision(
+        &self,
+        did: &str,
+        uri: &str,
+        reason: &str,
+        details: &str,
+    ) -> Result<()> {
+        sqlx::query!(
+            "INSERT INTO SpamDecision (did, uri, reason, details) VALUES ($1, $2, $3, $4)",
+            did,
+            uri,
+            reason,
+            details
+        )
+        .execute(&self.connection_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Fetches the most recent posts that were taken for spam, for reviewing false positives.
+    pub async fn fetch_spam_decisions(&self, limit: usize) -> Result<Vec<SpamDecision>> {
+        Ok(sqlx::query_as!(
+            SpamDecision,
+            r#"
+            SELECT did, uri, reason, details, decided_at FROM SpamDecision
+            ORDER BY decided_at DESC, id DESC
+            LIMIT $1
+            "#,
+            limit as i64
+        )
+        .fetch_all(&self.connection_pool)
+        .await?)
+    }
+
+    pub async fn fetch_list_items(&self) -> Result<Vec<ListItem>> {
+        Ok(
+            sqlx::query_as!(ListItem, "SELECT uri, list_uri, subject_did FROM ListItem")
+                .fetch_all(&self.connection_pool)
+                .await?,
+        )
+    }
+
+    pub async fn insert_list_item(&self, item: &ListItem) -> Result<bool> {
+        Ok(sqlx::query!(
+            "INSERT INTO ListItem (uri, list_uri, subject_did) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+            item.uri,
+            item.list_uri,
+            item.subject_did
+        )
+        .execute(&self.connection_pool)
+        .await
+        .map(|result| result.rows_affected() > 0)?)
+    }
+
+    /// Deletes a list item, returning it if it existed.
+    pub async fn delete_list_item(&self, uri: &str) -> Result<Option<ListItem>> {
+        Ok(sqlx::query_as!(
+            ListItem,
+            "DELETE FROM ListItem WHERE uri = $1 RETURNING uri, list_uri, subject_did",
+            uri
+        )
+        .fetch_optional(&self.connection_pool)
+        .await?)
+    }
+
+    pub async fn is_list_member(&self, list_uri: &str, did: &str) -> Result<bool> {
+        Ok(sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM ListItem WHERE list_uri = $1 AND subject_did = $2) AS "exists!""#,
+            list_uri,
+            did
+        )
+        .fetch_one(&self.connection_pool)
+        .await?)
+    }
+
+    /// Replaces all list items stored for the given repo with the given ones.
+    pub async fn replace_list_items(&self, repo_did: &str, items: &[ListItem]) -> Result<()> {
+        let mut transaction = self.connection_pool.begin().await?;
+
+        sqlx::query!(
+            "DELETE FROM ListItem WHERE uri LIKE 'at://' || $1 || '/%'",
+            repo_did
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        for item in items {
+            sqlx::query!(
+                "INSERT INTO ListItem (uri, list_uri, subject_did) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+                item.uri,
+                item.list_uri,
+                item.subject_did
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+
+        Ok(transaction.commit().await?)
+    }
+
+    /// Stores a label from a labeler, as long as it's on a post or an author that we know.
+    pub async fn insert_label(
+        &self,
+        src: &str,
+        uri: &str,
+        val: &str,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<bool> {
+        Ok(sqlx::query!(
+            r#"
+            INSERT INTO Label (src, uri, val, expires_at)
+            SELECT $1, $2, $3, $4
+            WHERE EXISTS (SELECT 1 FROM Post WHERE uri = $2)
+                OR EXISTS (SELECT 1 FROM Profile WHERE did = $2)
+            ON CONFLICT (src, uri, val) DO UPDATE SET expires_at = EXCLUDED.expires_at
+            "#,
+            src,
+            uri,
+            val,
+            expires_at
+        )
+        .execute(&self.connection_pool)
+        .await
+        .map(|result| result.rows_affected() > 0)?)
+    }
+
+    pub async fn delete_label(&self, src: &str, uri: &str, val: &str) -> Result<bool> {
+        Ok(sqlx::query!(
+            "DELETE FROM Label WHERE src = $1 AND uri = $2 AND val = $3",
+            src,
+            uri,
+            val
+        )
+        .execute(&self.connection_pool)
+        .await
+        .map(|result| result.rows_affected() > 0)?)
+    }
+
+    pub async fn fetch_indexing_stats(&self) -> Result<IndexingStats> {
+        let posts = sqlx::query!(
+            r#"
+            SELECT
+                COUNT(*) AS "posts!",
+                COUNT(*) FILTER (WHERE indexed_at > NOW() - INTERVAL '1 day') AS "posts_last_day!",
+                MAX(indexed_at) AS latest_indexed_at
+            FROM Post
+            "#
+        )
+        .fetch_one(&self.connection_pool)
+        .await?;
+
+        let profiles = sqlx::query!(
+            r#"
+            SELECT
+                COUNT(*) AS "profiles!",
+                COUNT(*) FILTER (
+                    WHERE has_been_processed = FALSE
+                        AND did NOT IN (SELECT did FROM ProfileCountryOverride)
+                ) AS "unprocessed_profiles!"
+            FROM Profile
+            "#
+        )
+        .fetch_one(&self.connection_pool)
+        .await?;
+
+        let feeds = sqlx::query_as!(
+            FeedStats,
+            r#"
+            SELECT
+                FeedPost.feed,
+                COUNT(*) AS "posts!",
+                COUNT(*) FILTER (WHERE Post.indexed_at > NOW() - INTERVAL '1 day') AS "posts_last_day!"
+            FROM FeedPost
+            JOIN Post ON Post.uri = FeedPost.uri
+            GROUP BY FeedPost.feed
+            ORDER BY FeedPost.feed
+            "#
+        )
+        .fetch_all(&self.connection_pool)
+        .await?;
+
+        Ok(IndexingStats {
+            posts: posts.posts,
+            posts_last_day: posts.posts_last_day,
+            latest_indexed_at: posts.latest_indexed_at,
+            profiles: profiles.profiles,
+            unprocessed_profiles: profiles.unprocessed_profiles,
+            feeds,
+        })
+    }
+
     pub async fn fetch_subscription_cursor(&self, host: &str, did: &str) -> Result<Option<i64>> {
         Ok(sqlx::query!(
             "SELECT cursor FROM SubscriptionState WHERE service = $1 AND host = $2",
@@ -87,3 +1202,204 @@ impl Database {
         .map(|result| result.rows_affected() > 0)?)
     }
 }
+
+async fn log_moderation_action(
+    connection: &mut PgConnection,
+    action: ModerationActionKind,
+    subject: &str,
+    reason: Option<&str>,
+    performed_by: &str,
+) -> Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO ModerationAction (action, subject, reason, performed_by)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        action.as_str(),
+        subject,
+        reason,
+        performed_by
+    )
+    .execute(connection)
+    .await?;
+
+    Ok(())
+}
+
+/// Updates the stored rank of posts in feeds, either of the given posts or of every
+/// post that has any engagement at all. Posts in feeds served newest first are ranked
+/// at the time that they were indexed.
+async fn rank_feed_posts(
+    connection: &mut PgConnection,
+    rankings: &[(&str, Ranking)],
+    uris: Option<&[String]>,
+) -> Result<()> {
+    if rankings.is_empty() || uris.is_some_and(|uris| uris.is_empty()) {
+        return Ok(());
+    }
+
+    let (feeds, gravities): (Vec<&str>, Vec<f64>) = rankings
+        .iter()
+        .map(|(feed, ranking)| (*feed, ranking.gravity_secs()))
+        .unzip();
+
+    sqlx::query!(
+        r#"
+        UPDATE FeedPost SET ranked_at = Ranked.ranked_at
+        FROM (
+            SELECT
+                FeedPost.id,
+                Post.indexed_at + make_interval(
+                    secs => Feed.gravity * LN(1 + Post.like_count + 2 * Post.repost_count) / LN(2)
+                ) AS ranked_at
+            FROM FeedPost
+            INNER JOIN Post ON Post.uri = FeedPost.uri
+            INNER JOIN UNNEST($1::TEXT[], $2::FLOAT8[]) AS Feed (feed, gravity)
+                ON Feed.feed = FeedPost.feed
+            WHERE CASE
+                WHEN $3::TEXT[] IS NULL THEN Post.like_count + Post.repost_count > 0
+                ELSE Post.uri = ANY($3)
+            END
+        ) AS Ranked
+        WHERE FeedPost.id = Ranked.id AND FeedPost.ranked_at <> Ranked.ranked_at
+        "#,
+        &feeds as &[&str],
+        &gravities,
+        uris
+    )
+    .execute(connection)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::TestDatabase;
+    use super::*;
+
+    fn post_uri(author_did: &str, rkey: &str) -> String {
+        format!("at://{author_did}/app.bsky.feed.post/{rkey}")
+    }
+
+    async fn feed_uris(database: &Database, feed: &str, filter: &FeedFilter<'_>) -> Vec<String> {
+        database
+            .fetch_feed_posts(feed, filter, 100, None)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|post| post.uri)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn leaves_out_posts_of_authors_taken_off_the_list() {
+        let Some(database) = TestDatabase::create().await else {
+            return;
+        };
+
+        let list_uri = "at://did:plc:publisher/app.bsky.graph.list/curated";
+
+        for (rkey, did) in [("1", "did:plc:stays"), ("2", "did:plc:leaves")] {
+            database
+                .insert_list_item(&ListItem {
+                    uri: format!("at://did:plc:publisher/app.bsky.graph.listitem/{rkey}"),
+                    list_uri: list_uri.to_owned(),
+                    subject_did: did.to_owned(),
+                })
+                .await
+                .unwrap();
+
+            database
+                .insert_post(did, rkey, &post_uri(did, rkey), &["curated"])
+                .await
+                .unwrap();
+        }
+
+        let filter = FeedFilter {
+            list_uri: Some(list_uri),
+            ..Default::default()
+        };
+
+        assert_eq!(feed_uris(&database, "curated", &filter).await.len(), 2);
+
+        database
+            .delete_list_item("at://did:plc:publisher/app.bsky.graph.listitem/2")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            feed_uris(&database, "curated", &filter).await,
+            vec![post_uri("did:plc:stays", "1")]
+        );
+
+        database.drop().await;
+    }
+
+    #[tokio::test]
+    async fn filters_pinned_posts_like_the_rest_of_the_feed() {
+        let Some(database) = TestDatabase::create().await else {
+            return;
+        };
+
+        let dutch = post_uri("did:plc:dutch", "1");
+        let belgian = post_uri("did:plc:belgian", "1");
+        let unwanted = post_uri("did:plc:dutch", "2");
+
+        for (did, rkey) in [
+            ("did:plc:dutch", "1"),
+            ("did:plc:belgian", "1"),
+            ("did:plc:dutch", "2"),
+        ] {
+            let uri = post_uri(did, rkey);
+            let cid = format!("{did}/{rkey}");
+
+            database
+                .insert_post(did, &cid, &uri, &["feed"])
+                .await
+                .unwrap();
+            database.pin_post("feed", &uri, "admin-api").await.unwrap();
+        }
+
+        database
+            .store_profile_country("did:plc:dutch", "nl")
+            .await
+            .unwrap();
+        database
+            .store_profile_country("did:plc:belgian", "be")
+            .await
+            .unwrap();
+
+        database
+            .insert_interactions(
+                "did:plc:viewer",
+                &[Interaction {
+                    post_uri: unwanted.clone(),
+                    event: REQUEST_LESS.to_owned(),
+                    feed_context: None,
+                }],
+            )
+            .await
+            .unwrap();
+
+        let countries = ["nl".to_owned()];
+        let filter = FeedFilter {
+            countries: Some(&countries),
+            viewer_did: Some("did:plc:viewer"),
+            ..Default::default()
+        };
+
+        let pins = database.fetch_feed_pins("feed", &filter, 10).await.unwrap();
+        assert_eq!(pins, vec![dutch.clone()]);
+
+        // Pinned posts aren't served again among the others
+        let filter = FeedFilter {
+            excluded_uris: &pins,
+            viewer_did: Some("did:plc:viewer"),
+            ..Default::default()
+        };
+        assert_eq!(feed_uris(&database, "feed", &filter).await, vec![belgian]);
+
+        database.drop().await;
+    }
+}
@@ -0,0 +1,90 @@
+//! Scratch databases for tests that need to run queries.
+
+use std::ops::Deref;
+
+use chrono::{DateTime, Utc};
+use sqlx::postgres::{PgConnectOptions, PgConnection, PgPoolOptions};
+use sqlx::{Connection, Executor};
+
+use super::Database;
+
+/// A database of its own for a test, with every migration applied, created on the
+/// server that `TEST_DATABASE_URL` points to. Tests that need one are skipped when
+/// it isn't set.
+pub struct TestDatabase {
+    database: Database,
+    server: PgConnectOptions,
+    name: String,
+}
+
+impl TestDatabase {
+    pub async fn create() -> Option<Self> {
+        let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+            eprintln!("Skipping a database test, TEST_DATABASE_URL isn't set");
+            return None;
+        };
+
+        let server: PgConnectOptions = url.parse().expect("TEST_DATABASE_URL is invalid");
+        let name = format!("nederlandskie_test_{}", rand::random::<u32>());
+
+        PgConnection::connect_with(&server)
+            .await
+            .unwrap()
+            .execute(format!("CREATE DATABASE {name}").as_str())
+            .await
+            .unwrap();
+
+        let connection_pool = PgPoolOptions::new()
+            .connect_with(server.clone().database(&name))
+            .await
+            .unwrap();
+
+        let mut migrations: Vec<_> = std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/sql"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        migrations.sort();
+
+        for migration in migrations {
+            sqlx::raw_sql(&std::fs::read_to_string(migration).unwrap())
+                .execute(&connection_pool)
+                .await
+                .unwrap();
+        }
+
+        Some(Self {
+            database: Database { connection_pool },
+            server,
+            name,
+        })
+    }
+
+    /// Moves a post to the given point in time in all feeds that it's in.
+    pub async fn rank_post_at(&self, uri: &str, ranked_at: DateTime<Utc>) {
+        sqlx::query("UPDATE FeedPost SET ranked_at = $1 WHERE uri = $2")
+            .bind(ranked_at)
+            .bind(uri)
+            .execute(&self.database.connection_pool)
+            .await
+            .unwrap();
+    }
+
+    pub async fn drop(self) {
+        self.database.connection_pool.close().await;
+
+        PgConnection::connect_with(&self.server)
+            .await
+            .unwrap()
+            .execute(format!("DROP DATABASE {} WITH (FORCE)", self.name).as_str())
+            .await
+            .unwrap();
+    }
+}
+
+impl Deref for TestDatabase {
+    type Target = Database;
+
+    fn deref(&self) -> &Self::Target {
+        &self.database
+    }
+}
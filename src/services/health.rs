@@ -0,0 +1,171 @@
+use std::sync::Mutex;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+
+use crate::services::bluesky::CommitDetails;
+
+/// How long indexing can go without saving its progress before it's considered stalled.
+///
+/// Cursors get saved every handful of commits, and there are dozens of commits on the
+/// firehose every second, so a few minutes without any means nothing is being indexed.
+const MAX_CURSOR_WRITE_AGE: Duration = Duration::minutes(5);
+
+/// How long the firehose can stay disconnected before indexing is considered stalled,
+/// so that reconnecting now and then doesn't get an instance restarted.
+const MAX_DISCONNECTION: Duration = Duration::minutes(1);
+
+#[derive(Default)]
+struct IndexingState {
+    firehose_connected: bool,
+    /// When the firehose got disconnected, unless it never was connected
+    disconnected_at: Option<DateTime<Utc>>,
+    /// Sequence number and time of the last commit that was indexed
+    last_indexed: Option<(i64, DateTime<Utc>)>,
+    cursor_written_at: Option<DateTime<Utc>>,
+}
+
+/// Keeps track of whether indexing is keeping up, so that an instance that got
+/// wedged can be told apart and restarted.
+pub struct Health {
+    started_at: DateTime<Utc>,
+    indexing: Mutex<IndexingState>,
+}
+
+/// How indexing is doing, as reported to the orchestrator.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct IndexingHealth {
+    pub firehose_connected: bool,
+    pub indexed_seq: Option<i64>,
+    /// How long ago the last commit that was indexed was made
+    pub lag_seconds: Option<f64>,
+    pub cursor_written_at: Option<DateTime<Utc>>,
+    pub stalled: bool,
+}
+
+impl Health {
+    pub fn new() -> Self {
+        Self {
+            started_at: Utc::now(),
+            indexing: Default::default(),
+        }
+    }
+
+    pub fn set_firehose_connected(&self, connected: bool, time: DateTime<Utc>) {
+        let mut state = self.lock();
+
+        state.disconnected_at = match connected {
+            true => None,
+            false => state.disconnected_at.or(Some(time)),
+        };
+        state.firehose_connected = connected;
+    }
+
+    pub fn observe_indexed_commit(&self, commit: &CommitDetails) {
+        self.lock().last_indexed = Some((commit.seq, commit.time));
+    }
+
+    pub fn observe_cursor_write(&self, time: DateTime<Utc>) {
+        self.lock().cursor_written_at = Some(time);
+    }
+
+    /// Reports how indexing is doing at the given time.
+    ///
+    /// Falling behind the firehose doesn't count as being stalled, since that's what
+    /// catching up after a restart looks like too, but not saving any progress does,
+    /// and neither does staying disconnected from the firehose for a while.
+    pub fn indexing(&self, now: DateTime<Utc>) -> IndexingHealth {
+        let state = self.lock();
+
+        let progressed_at = state.cursor_written_at.unwrap_or(self.started_at);
+        let disconnected_at =
+            (!state.firehose_connected).then(|| state.disconnected_at.unwrap_or(self.started_at));
+
+        IndexingHealth {
+            firehose_connected: state.firehose_connected,
+            indexed_seq: state.last_indexed.map(|(seq, _)| seq),
+            lag_seconds: state
+                .last_indexed
+                .map(|(_, time)| (now - time).num_milliseconds() as f64 / 1000.0),
+            cursor_written_at: state.cursor_written_at,
+            stalled: disconnected_at.is_some_and(|time| now - time > MAX_DISCONNECTION)
+                || now - progressed_at > MAX_CURSOR_WRITE_AGE,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, IndexingState> {
+        self.indexing.lock().expect("health lock is poisoned")
+    }
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(seq: i64, time: DateTime<Utc>) -> CommitDetails {
+        CommitDetails {
+            seq,
+            time,
+            operations: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn reports_stalled_indexing() {
+        let health = Health::new();
+        let now = health.started_at;
+
+        // Not connected to the firehose yet, for too long
+        assert!(!health.indexing(now).stalled);
+        assert!(health.indexing(now + Duration::minutes(2)).stalled);
+
+        health.set_firehose_connected(true, now);
+        assert!(!health.indexing(now).stalled);
+
+        // Connected, but nothing has been saved since starting up
+        assert!(health.indexing(now + Duration::minutes(6)).stalled);
+
+        health.observe_indexed_commit(&commit(42, now + Duration::minutes(4)));
+        health.observe_cursor_write(now + Duration::minutes(5));
+
+        assert_eq!(
+            health.indexing(now + Duration::minutes(6)),
+            IndexingHealth {
+                firehose_connected: true,
+                indexed_seq: Some(42),
+                lag_seconds: Some(120.0),
+                cursor_written_at: Some(now + Duration::minutes(5)),
+                stalled: false,
+            }
+        );
+        assert!(health.indexing(now + Duration::minutes(11)).stalled);
+
+        health.set_firehose_connected(false, now + Duration::minutes(6));
+        assert!(health.indexing(now + Duration::minutes(8)).stalled);
+    }
+
+    #[test]
+    fn gives_the_firehose_time_to_reconnect() {
+        let health = Health::new();
+        let now = health.started_at;
+
+        health.set_firehose_connected(true, now);
+        health.observe_cursor_write(now);
+
+        health.set_firehose_connected(false, now + Duration::seconds(10));
+        assert!(!health.indexing(now + Duration::seconds(30)).stalled);
+
+        // Failing to reconnect doesn't restart the clock
+        health.set_firehose_connected(false, now + Duration::seconds(40));
+        assert!(health.indexing(now + Duration::seconds(80)).stalled);
+
+        health.set_firehose_connected(true, now + Duration::seconds(90));
+        assert!(!health.indexing(now + Duration::seconds(90)).stalled);
+    }
+}
@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use lingua::{IsoCode639_1, Language, LanguageDetectorBuilder};
+
+use super::bluesky::PostFeatures;
+
+/// How much the languages declared by the author weigh against the ones detected from
+/// the text when both are available. Clients tend to declare the UI language
+/// regardless of what's actually written, so the text gets the final say.
+const DECLARED_LANGUAGES_WEIGHT: f64 = 0.3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DetectedLanguage {
+    pub language: Language,
+    pub confidence: f64,
+}
+
+/// Relative confidence of every candidate language for a piece of text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LanguageScores {
+    /// Sorted from the most to the least likely language
+    scores: Vec<DetectedLanguage>,
+    text_length: usize,
+}
+
+impl LanguageScores {
+    pub fn most_likely(&self) -> Option<DetectedLanguage> {
+        self.scores.first().copied()
+    }
+
+    pub fn confidence_of(&self, language: Language) -> f64 {
+        self.scores
+            .iter()
+            .find(|detected| detected.language == language)
+            .map_or(0.0, |detected| detected.confidence)
+    }
+
+    /// Number of characters the detection was based on, after links,
+    /// mentions and hashtags were removed.
+    pub fn text_length(&self) -> usize {
+        self.text_length
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &DetectedLanguage> {
+        self.scores.iter()
+    }
+}
+
+/// Requirements that an algo puts on the language of the posts it accepts.
+#[derive(Debug, Clone)]
+pub struct LanguageFilter {
+    pub languages: Vec<Language>,
+    pub min_confidence: f64,
+    pub min_text_length: usize,
+}
+
+impl LanguageFilter {
+    /// Whether the text is long enough to be trusted and most likely written in one of
+    /// the wanted languages with at least the minimum confidence.
+    pub fn matches(&self, scores: &LanguageScores) -> bool {
+        if scores.text_length() < self.min_text_length {
+            return false;
+        }
+
+        scores.most_likely().is_some_and(|detected| {
+            self.languages.contains(&detected.language)
+                && detected.confidence >= self.min_confidence
+        })
+    }
+}
+
+/// Determines the language of posts by combining the languages declared by the author
+/// with detection over the text itself.
+///
+/// Detection is restricted to a set of candidate languages: confidence is relative to
+/// the other candidates only, and declared languages outside of the set are ignored.
+/// Closely related languages, such as Russian, Ukrainian and Belarusian, should all be
+/// candidates for them to be told apart.
+pub struct LanguageDetector {
+    detector: lingua::LanguageDetector,
+    candidates: Vec<Language>,
+}
+
+impl LanguageDetector {
+    /// Languages that our feeds care about, along with the ones most commonly
+    /// mistaken for them.
+    pub const DEFAULT_CANDIDATES: &'static [Language] = &[
+        Language::Russian,
+        Language::Ukrainian,
+        Language::Belarusian,
+        Language::Bulgarian,
+        Language::Dutch,
+        Language::English,
+        Language::German,
+    ];
+
+    /// Creates a detector for the given candidate languages.
+    ///
+    /// Panics if fewer than two candidates are given.
+    pub fn new(candidates: &[Language]) -> Self {
+        Self {
+            detector: LanguageDetectorBuilder::from_languages(candidates).build(),
+            candidates: candidates.to_vec(),
+        }
+    }
+
+    pub fn candidates(&self) -> &[Language] {
+        &self.candidates
+    }
+
+    /// Detects the language of everything the author wrote in the post,
+    /// alt texts included.
+    pub fn detect_post(&self, post: &PostFeatures) -> LanguageScores {
+        let declared: Vec<Language> = post
+            .langs
+            .iter()
+            .filter_map(|lang| parse_language_tag(lang))
+            .collect();
+
+        self.detect(&post.authored_text(), &declared)
+    }
+
+    pub fn detect(&self, text: &str, declared: &[Language]) -> LanguageScores {
+        let text = strip_non_prose(text);
+        let text_length = text.chars().count();
+
+        let declared: Vec<Language> = declared
+            .iter()
+            .filter(|language| self.candidates.contains(language))
+            .copied()
+            .collect();
+
+        let detected = if text.is_empty() {
+            Vec::new()
+        } else {
+            self.detector.compute_language_confidence_values(text)
+        };
+
+        // lingua reports all zeroes when it can't make anything of the text
+        let has_detected = detected.iter().any(|(_, confidence)| *confidence > 0.0);
+
+        let (detected_weight, declared_weight) = match (has_detected, declared.is_empty()) {
+            (_, true) => (1.0, 0.0),
+            (false, false) => (0.0, 1.0),
+            (true, false) => (1.0 - DECLARED_LANGUAGES_WEIGHT, DECLARED_LANGUAGES_WEIGHT),
+        };
+
+        let mut scores: HashMap<Language, f64> = HashMap::new();
+
+        for (language, confidence) in detected {
+            *scores.entry(language).or_default() += confidence * detected_weight;
+        }
+
+        for language in &declared {
+            *scores.entry(*language).or_default() += declared_weight / declared.len() as f64;
+        }
+
+        let mut scores: Vec<DetectedLanguage> = scores
+            .into_iter()
+            .filter(|(_, confidence)| *confidence > 0.0)
+            .map(|(language, confidence)| DetectedLanguage {
+                language,
+                confidence,
+            })
+            .collect();
+
+        scores.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+
+        LanguageScores {
+            scores,
+            text_length,
+        }
+    }
+}
+
+/// Parses a BCP-47 language tag such as `ru` or `nl-NL` into a language,
+/// looking only at the primary language subtag. Tags are case-insensitive,
+/// so `NL` and `en-GB` are understood too.
+pub fn parse_language_tag(tag: &str) -> Option<Language> {
+    let primary = tag.split('-').next()?.to_ascii_lowercase();
+
+    IsoCode639_1::from_str(&primary)
+        .ok()
+        .map(|iso_code| Language::from_iso_code_639_1(&iso_code))
+}
+
+/// Removes parts of the text that don't say anything about its language:
+/// links, mentions and hashtags.
+fn strip_non_prose(text: &str) -> String {
+    text.split_whitespace()
+        .filter(|word| {
+            !(word.starts_with("http://")
+                || word.starts_with("https://")
+                || word.starts_with("www.")
+                || word.starts_with('@')
+                || word.starts_with('#'))
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detector() -> LanguageDetector {
+        LanguageDetector::new(LanguageDetector::DEFAULT_CANDIDATES)
+    }
+
+    #[test]
+    fn strips_links_mentions_and_hashtags() {
+        assert_eq!(
+            strip_non_prose("Look @alice.bsky.social https://example.com #amsterdam it's\nnice"),
+            "Look it's nice"
+        );
+        assert_eq!(strip_non_prose("#one #two www.example.com"), "");
+    }
+
+    #[test]
+    fn parses_language_tags() {
+        assert_eq!(parse_language_tag("ru"), Some(Language::Russian));
+        assert_eq!(parse_language_tag("nl-NL"), Some(Language::Dutch));
+        assert_eq!(parse_language_tag("x-klingon"), None);
+        assert_eq!(parse_language_tag("NL"), Some(Language::Dutch));
+        assert_eq!(parse_language_tag("en-GB"), Some(Language::English));
+    }
+
+    #[test]
+    fn falls_back_to_declared_languages() {
+        let detector = detector();
+
+        let scores = detector.detect("https://example.com #news", &[Language::Russian]);
+        let detected = scores
+            .most_likely()
+            .expect("declared language should be used");
+
+        assert_eq!(detected.language, Language::Russian);
+        assert_eq!(detected.confidence, 1.0);
+        assert_eq!(scores.text_length(), 0);
+        assert_eq!(detector.detect("@someone", &[]).most_likely(), None);
+    }
+
+    #[test]
+    fn ignores_declared_languages_outside_of_candidates() {
+        let scores = detector().detect("", &[Language::Japanese]);
+
+        assert_eq!(scores.most_likely(), None);
+    }
+
+    #[test]
+    fn prefers_text_over_declared_languages() {
+        let scores = detector().detect(
+            "Всем привет! Сегодня гуляли по Амстердаму, погода была просто чудесная.",
+            &[Language::English],
+        );
+
+        assert_eq!(scores.most_likely().unwrap().language, Language::Russian);
+    }
+
+    #[test]
+    fn tells_russian_from_ukrainian_and_belarusian() {
+        let detector = detector();
+
+        let detect = |text| detector.detect(text, &[]).most_likely().unwrap().language;
+
+        assert_eq!(
+            detect("Сегодня вечером мы идём в кино, присоединяйтесь!"),
+            Language::Russian
+        );
+        assert_eq!(
+            detect("Сьогодні ввечері ми йдемо в кіно, приєднуйтесь!"),
+            Language::Ukrainian
+        );
+        assert_eq!(
+            detect("Сёння ўвечары мы ідзём у кіно, далучайцеся!"),
+            Language::Belarusian
+        );
+    }
+
+    #[test]
+    fn filters_on_confidence_and_length() {
+        let filter = LanguageFilter {
+            languages: vec![Language::Russian],
+            min_confidence: 0.7,
+            min_text_length: 20,
+        };
+
+        let detector = detector();
+
+        assert!(filter.matches(&detector.detect(
+            "Кто-нибудь знает хорошего стоматолога в Утрехте?",
+            &[Language::Russian]
+        )));
+        assert!(!filter.matches(&detector.detect("Привет!", &[Language::Russian])));
+        assert!(!filter.matches(&detector.detect(
+            "Weet iemand een goede tandarts in Utrecht? Срочно нужно",
+            &[]
+        )));
+    }
+}
@@ -0,0 +1,58 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::RwLock;
+
+/// Members of the Bluesky lists that curated feeds are based on.
+///
+/// Membership has to be checked for every post coming from the firehose, so it's
+/// kept in memory rather than looked up in the database each time.
+#[derive(Default)]
+pub struct ListMembers {
+    members: RwLock<HashMap<String, HashSet<String>>>,
+}
+
+impl ListMembers {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn contains(&self, list_uri: &str, did: &str) -> bool {
+        self.members
+            .read()
+            .expect("list members lock is poisoned")
+            .get(list_uri)
+            .is_some_and(|members| members.contains(did))
+    }
+
+    pub fn add(&self, list_uri: &str, did: &str) {
+        self.members
+            .write()
+            .expect("list members lock is poisoned")
+            .entry(list_uri.to_owned())
+            .or_default()
+            .insert(did.to_owned());
+    }
+
+    pub fn remove(&self, list_uri: &str, did: &str) {
+        if let Some(members) = self
+            .members
+            .write()
+            .expect("list members lock is poisoned")
+            .get_mut(list_uri)
+        {
+            members.remove(did);
+        }
+    }
+
+    /// Replaces all known members with the given pairs of list URI and member DID.
+    pub fn replace_all<'a>(&self, items: impl IntoIterator<Item = (&'a str, &'a str)>) {
+        let mut members: HashMap<String, HashSet<String>> = HashMap::new();
+        for (list_uri, did) in items {
+            members
+                .entry(list_uri.to_owned())
+                .or_default()
+                .insert(did.to_owned());
+        }
+
+        *self.members.write().expect("list members lock is poisoned") = members;
+    }
+}
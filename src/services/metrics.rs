@@ -0,0 +1,226 @@
+use std::time::Duration;
+
+use atrium_api::types::Collection;
+use chrono::Utc;
+use prometheus::{
+    Encoder, Gauge, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+
+use crate::services::bluesky::{CommitDetails, Operation};
+
+const NAMESPACE: &str = "nederlandskie";
+
+/// Everything we keep track of about how the feed generator is doing, for Prometheus
+/// to scrape.
+///
+/// Rates, like firehose events per second, are left for Prometheus to work out
+/// from the counters.
+pub struct Metrics {
+    registry: Registry,
+    firehose_operations: IntCounterVec,
+    indexed_seq: IntGauge,
+    indexing_lag: Gauge,
+    indexer_queue_depth: IntGauge,
+    algo_decisions: IntCounterVec,
+    ai_requests: HistogramVec,
+    database_connections: IntGaugeVec,
+    feed_requests: HistogramVec,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new_custom(Some(NAMESPACE.to_owned()), None)
+            .expect("metrics namespace should be valid");
+
+        let metrics = Self {
+            firehose_operations: IntCounterVec::new(
+                Opts::new(
+                    "firehose_operations_total",
+                    "Operations received from the firehose",
+                ),
+                &["collection", "action"],
+            )
+            .expect("metric should be valid"),
+            indexed_seq: IntGauge::new(
+                "indexed_seq",
+                "Sequence number of the last firehose commit that was indexed",
+            )
+            .expect("metric should be valid"),
+            indexing_lag: Gauge::new(
+                "indexing_lag_seconds",
+                "How long ago the last firehose commit that was indexed was made",
+            )
+            .expect("metric should be valid"),
+            indexer_queue_depth: IntGauge::new(
+                "indexer_queue_depth",
+                "Firehose commits waiting to be indexed",
+            )
+            .expect("metric should be valid"),
+            algo_decisions: IntCounterVec::new(
+                Opts::new(
+                    "algo_decisions_total",
+                    "Posts that feeds accepted or rejected",
+                ),
+                &["feed", "decision"],
+            )
+            .expect("metric should be valid"),
+            ai_requests: HistogramVec::new(
+                HistogramOpts::new(
+                    "ai_request_duration_seconds",
+                    "How long inferring countries of living takes",
+                )
+                .buckets(vec![0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0]),
+                &["outcome"],
+            )
+            .expect("metric should be valid"),
+            database_connections: IntGaugeVec::new(
+                Opts::new("database_connections", "Connections in the database pool"),
+                &["state"],
+            )
+            .expect("metric should be valid"),
+            feed_requests: HistogramVec::new(
+                HistogramOpts::new(
+                    "feed_request_duration_seconds",
+                    "How long serving feed skeletons takes",
+                ),
+                &["feed", "status"],
+            )
+            .expect("metric should be valid"),
+            registry,
+        };
+
+        for collector in [
+            Box::new(metrics.firehose_operations.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(metrics.indexed_seq.clone()),
+            Box::new(metrics.indexing_lag.clone()),
+            Box::new(metrics.indexer_queue_depth.clone()),
+            Box::new(metrics.algo_decisions.clone()),
+            Box::new(metrics.ai_requests.clone()),
+            Box::new(metrics.database_connections.clone()),
+            Box::new(metrics.feed_requests.clone()),
+        ] {
+            metrics
+                .registry
+                .register(collector)
+                .expect("metrics should be registered only once");
+        }
+
+        metrics
+    }
+
+    /// Counts the operations of a commit as it comes in from the firehose.
+    pub fn observe_firehose_commit(&self, commit: &CommitDetails) {
+        for operation in &commit.operations {
+            let (collection, action) = match operation {
+                Operation::Create { collection, .. } => (collection, "create"),
+                Operation::Update { collection, .. } => (collection, "update"),
+                Operation::Delete { collection, .. } => (collection, "delete"),
+            };
+
+            self.firehose_operations
+                .with_label_values(&[collection_label(collection), action])
+                .inc();
+        }
+    }
+
+    /// Takes note of a commit having been indexed, with the given number of commits
+    /// still waiting after it.
+    pub fn observe_indexed_commit(&self, commit: &CommitDetails, queue_depth: usize) {
+        self.indexed_seq.set(commit.seq);
+        self.indexing_lag
+            .set((Utc::now() - commit.time).num_milliseconds() as f64 / 1000.0);
+        self.indexer_queue_depth.set(queue_depth as i64);
+    }
+
+    pub fn observe_algo_decision(&self, feed: &str, accepted: bool) {
+        self.algo_decisions
+            .with_label_values(&[feed, if accepted { "accepted" } else { "rejected" }])
+            .inc();
+    }
+
+    pub fn observe_ai_request(&self, duration: Duration, succeeded: bool) {
+        self.ai_requests
+            .with_label_values(&[if succeeded { "success" } else { "error" }])
+            .observe(duration.as_secs_f64());
+    }
+
+    pub fn observe_feed_request(&self, feed: &str, status: u16, duration: Duration) {
+        self.feed_requests
+            .with_label_values(&[feed, &status.to_string()])
+            .observe(duration.as_secs_f64());
+    }
+
+    pub fn observe_database_pool(&self, size: u32, idle: usize) {
+        self.database_connections
+            .with_label_values(&["idle"])
+            .set(idle as i64);
+        self.database_connections
+            .with_label_values(&["in_use"])
+            .set(size as i64 - idle as i64);
+    }
+
+    /// Renders everything in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("metrics should be encodable");
+
+        String::from_utf8(buffer).expect("metrics should be valid UTF-8")
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Only the collections that get indexed have time series of their own. The rest
+/// are lumped together, since anyone can make up new ones, even under `app.bsky`.
+fn collection_label(collection: &str) -> &str {
+    match collection {
+        atrium_api::app::bsky::feed::Post::NSID
+        | atrium_api::app::bsky::feed::Like::NSID
+        | atrium_api::app::bsky::feed::Repost::NSID
+        | atrium_api::app::bsky::actor::Profile::NSID
+        | atrium_api::app::bsky::graph::List::NSID
+        | atrium_api::app::bsky::graph::Listitem::NSID => collection,
+        _ => "other",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lumps_unknown_collections_together() {
+        assert_eq!(collection_label("app.bsky.feed.post"), "app.bsky.feed.post");
+        assert_eq!(
+            collection_label("app.bsky.graph.listitem"),
+            "app.bsky.graph.listitem"
+        );
+        assert_eq!(collection_label("app.bsky.made.up"), "other");
+        assert_eq!(collection_label("chat.bsky.actor.declaration"), "other");
+        assert_eq!(collection_label("com.example.whatever"), "other");
+    }
+
+    #[test]
+    fn renders_in_text_format() {
+        let metrics = Metrics::new();
+        metrics.observe_algo_decision("nederlandskie", true);
+        metrics.observe_feed_request("nederlandskie", 200, Duration::from_millis(30));
+
+        let rendered = metrics.render();
+
+        assert!(rendered.contains(
+            r#"nederlandskie_algo_decisions_total{decision="accepted",feed="nederlandskie"} 1"#
+        ));
+        assert!(rendered.contains(
+            r#"nederlandskie_feed_request_duration_seconds_count{feed="nederlandskie",status="200"} 1"#
+        ));
+    }
+}
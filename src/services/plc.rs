@@ -0,0 +1,62 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+pub const PLC_DIRECTORY: &str = "https://plc.directory";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AuditLogEntry {
+    created_at: DateTime<Utc>,
+}
+
+/// The directory that keeps track of `did:plc` identities.
+pub struct PlcDirectory {
+    client: reqwest::Client,
+}
+
+impl PlcDirectory {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .connect_timeout(CONNECT_TIMEOUT)
+                .build()
+                .expect("HTTP client should be buildable"),
+        }
+    }
+
+    /// Finds out when an account was created from the audit log of its DID, as long
+    /// as it's a `did:plc` that the directory knows about.
+    pub async fn fetch_account_created_at(&self, did: &str) -> Result<Option<DateTime<Utc>>> {
+        if !did.starts_with("did:plc:") {
+            return Ok(None);
+        }
+
+        let response = self
+            .client
+            .get(format!("{PLC_DIRECTORY}/{did}/log/audit"))
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        let log: Vec<AuditLogEntry> = response.error_for_status()?.json().await?;
+
+        Ok(log.iter().map(|entry| entry.created_at).min())
+    }
+}
+
+impl Default for PlcDirectory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,420 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+
+use crate::config::SpamConfig;
+use crate::services::bluesky::PostFeatures;
+use crate::services::PlcDirectory;
+
+/// How far back posting rates and duplicate texts are looked at.
+const ACTIVITY_WINDOW: Duration = Duration::hours(1);
+
+/// How often activity that's out of the window gets forgotten.
+const PRUNE_INTERVAL: Duration = Duration::minutes(5);
+
+/// Texts shorter than this are too likely to be posted by many people independently,
+/// like greetings, to be taken for spam.
+const MIN_DUPLICATE_TEXT_LENGTH: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpamReason {
+    NewAccount,
+    PostingTooOften,
+    DuplicateText,
+    LinkOnly,
+}
+
+impl SpamReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SpamReason::NewAccount => "new_account",
+            SpamReason::PostingTooOften => "posting_too_often",
+            SpamReason::DuplicateText => "duplicate_text",
+            SpamReason::LinkOnly => "link_only",
+        }
+    }
+}
+
+/// Why a post was taken for spam, for moderators to review.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpamVerdict {
+    pub reason: SpamReason,
+    pub details: String,
+}
+
+/// A post that went into feeds before it was known how old its author's account is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountAgeCheck {
+    pub did: String,
+    pub uri: String,
+    pub posted_at: DateTime<Utc>,
+}
+
+/// Authors and texts seen in the firehose recently.
+#[derive(Default)]
+struct Activity {
+    posts_by_author: HashMap<String, VecDeque<DateTime<Utc>>>,
+    /// Hashes of the DIDs of authors of every recent text, by the hash of the text
+    authors_by_text: HashMap<u64, (DateTime<Utc>, Vec<u64>)>,
+    pruned_at: Option<DateTime<Utc>>,
+}
+
+/// Tells apart posts of likely spammers and bots, so that they're kept out of every feed.
+///
+/// Posting rates and duplicate texts are judged by everything seen in the firehose
+/// within the last hour, so every post has to be observed, not just the ones that
+/// go into feeds.
+pub struct SpamFilter {
+    config: SpamConfig,
+    plc_directory: PlcDirectory,
+    activity: Mutex<Activity>,
+}
+
+impl SpamFilter {
+    pub fn new(config: SpamConfig) -> Self {
+        Self {
+            config,
+            plc_directory: PlcDirectory::new(),
+            activity: Default::default(),
+        }
+    }
+
+    /// Takes note of a post seen in the firehose at the given time.
+    pub fn observe(&self, author_did: &str, features: &PostFeatures, time: DateTime<Utc>) {
+        let mut activity = self
+            .activity
+            .lock()
+            .expect("spam activity lock is poisoned");
+
+        if activity
+            .pruned_at
+            .is_none_or(|pruned_at| time - pruned_at >= PRUNE_INTERVAL)
+        {
+            activity.prune(time);
+        }
+
+        if self.config.max_posts_per_hour > 0 {
+            let posts = activity
+                .posts_by_author
+                .entry(author_did.to_owned())
+                .or_default();
+
+            posts.push_back(time);
+
+            // Anything over the limit is as bad as being just over it
+            while posts.len() > self.config.max_posts_per_hour + 1 {
+                posts.pop_front();
+            }
+        }
+
+        if self.config.max_duplicate_authors > 0 {
+            if let Some(text_hash) = duplicate_text_hash(features) {
+                let (_, authors) = activity
+                    .authors_by_text
+                    .entry(text_hash)
+                    .or_insert_with(|| (time, Vec::new()));
+
+                let author_hash = hash(author_did);
+                if !authors.contains(&author_hash)
+                    && authors.len() <= self.config.max_duplicate_authors
+                {
+                    authors.push(author_hash);
+                }
+            }
+        }
+    }
+
+    /// Checks a post that was already observed against everything but the age of its author.
+    pub fn check(
+        &self,
+        author_did: &str,
+        features: &PostFeatures,
+        time: DateTime<Utc>,
+    ) -> Option<SpamVerdict> {
+        if self.config.reject_link_only_posts && is_link_only(features) {
+            return Some(SpamVerdict {
+                reason: SpamReason::LinkOnly,
+                details: "nothing but links".to_owned(),
+            });
+        }
+
+        let activity = self
+            .activity
+            .lock()
+            .expect("spam activity lock is poisoned");
+
+        if self.config.max_posts_per_hour > 0 {
+            let recent_posts = activity.posts_by_author.get(author_did).map_or(0, |posts| {
+                posts
+                    .iter()
+                    .filter(|posted_at| time - **posted_at < ACTIVITY_WINDOW)
+                    .count()
+            });
+
+            if recent_posts > self.config.max_posts_per_hour {
+                return Some(SpamVerdict {
+                    reason: SpamReason::PostingTooOften,
+                    details: format!(
+                        "more than {} posts in an hour",
+                        self.config.max_posts_per_hour
+                    ),
+                });
+            }
+        }
+
+        if self.config.max_duplicate_authors > 0 {
+            let authors = duplicate_text_hash(features)
+                .and_then(|text_hash| activity.authors_by_text.get(&text_hash))
+                .map_or(0, |(_, authors)| authors.len());
+
+            if authors > self.config.max_duplicate_authors {
+                return Some(SpamVerdict {
+                    reason: SpamReason::DuplicateText,
+                    details: format!(
+                        "same text posted by more than {} authors in an hour",
+                        self.config.max_duplicate_authors
+                    ),
+                });
+            }
+        }
+
+        None
+    }
+
+    /// How old accounts have to be, if their age is checked at all.
+    pub fn min_account_age(&self) -> Option<Duration> {
+        Some(Duration::seconds(
+            (self.config.min_account_age_hours * 3600.0) as i64,
+        ))
+        .filter(|age| *age > Duration::zero())
+    }
+
+    /// Checks the age of an account created at the given time.
+    pub fn check_account_age(
+        &self,
+        created_at: DateTime<Utc>,
+        time: DateTime<Utc>,
+    ) -> Option<SpamVerdict> {
+        let min_age = self.min_account_age()?;
+        let age = time - created_at;
+
+        (age < min_age).then(|| SpamVerdict {
+            reason: SpamReason::NewAccount,
+            details: format!("account is {} hours old", age.num_hours()),
+        })
+    }
+
+    /// Finds out when an account was created, if it can be known.
+    pub async fn fetch_account_created_at(&self, did: &str) -> Result<Option<DateTime<Utc>>> {
+        self.plc_directory.fetch_account_created_at(did).await
+    }
+}
+
+impl Activity {
+    fn prune(&mut self, time: DateTime<Utc>) {
+        self.posts_by_author.retain(|_, posts| {
+            posts
+                .back()
+                .is_some_and(|posted_at| time - *posted_at < ACTIVITY_WINDOW)
+        });
+
+        self.authors_by_text
+            .retain(|_, (first_seen_at, _)| time - *first_seen_at < ACTIVITY_WINDOW);
+
+        self.pruned_at = Some(time);
+    }
+}
+
+/// Hash of the text of a post, for texts long enough to be worth comparing.
+fn duplicate_text_hash(features: &PostFeatures) -> Option<u64> {
+    let text = features
+        .text
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+
+    (text.chars().count() >= MIN_DUPLICATE_TEXT_LENGTH).then(|| hash(&text))
+}
+
+fn hash(value: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Whether a post links somewhere without the author saying anything themselves.
+fn is_link_only(features: &PostFeatures) -> bool {
+    let has_links = features.external_link.is_some() || !features.links.is_empty();
+
+    let has_words = features
+        .authored_text()
+        .split_whitespace()
+        .filter(|word| !looks_like_link(word))
+        .any(|word| word.chars().any(char::is_alphanumeric));
+
+    has_links && !has_words
+}
+
+fn looks_like_link(word: &str) -> bool {
+    word.contains("://") || word.starts_with("www.") || (word.contains('.') && word.contains('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::services::bluesky::ExternalLink;
+
+    use super::*;
+
+    fn filter(config: SpamConfig) -> SpamFilter {
+        SpamFilter::new(config)
+    }
+
+    fn post(text: &str) -> PostFeatures {
+        PostFeatures {
+            text: text.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    fn at(minutes: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000, 0).unwrap() + Duration::minutes(minutes)
+    }
+
+    fn observe_and_check(
+        filter: &SpamFilter,
+        author_did: &str,
+        features: &PostFeatures,
+        time: DateTime<Utc>,
+    ) -> Option<SpamReason> {
+        filter.observe(author_did, features, time);
+        filter
+            .check(author_did, features, time)
+            .map(|verdict| verdict.reason)
+    }
+
+    #[test]
+    fn catches_authors_posting_too_often() {
+        let filter = filter(SpamConfig {
+            max_posts_per_hour: 3,
+            ..Default::default()
+        });
+
+        for minute in 0..3 {
+            assert_eq!(
+                observe_and_check(&filter, "did:plc:a", &post("hi"), at(minute)),
+                None
+            );
+        }
+
+        assert_eq!(
+            observe_and_check(&filter, "did:plc:a", &post("hi"), at(3)),
+            Some(SpamReason::PostingTooOften)
+        );
+        assert_eq!(
+            observe_and_check(&filter, "did:plc:b", &post("hi"), at(3)),
+            None
+        );
+
+        // An hour after the first post, the author is back under the limit
+        assert_eq!(
+            observe_and_check(&filter, "did:plc:a", &post("hi"), at(62)),
+            None
+        );
+    }
+
+    #[test]
+    fn catches_texts_posted_by_many_authors() {
+        let filter = filter(SpamConfig {
+            max_duplicate_authors: 2,
+            ..Default::default()
+        });
+
+        let spam = post("Claim your FREE tokens at the link below!");
+        let same_spam = post("claim your free tokens   at the link below!");
+
+        assert_eq!(observe_and_check(&filter, "did:plc:a", &spam, at(0)), None);
+        assert_eq!(observe_and_check(&filter, "did:plc:a", &spam, at(1)), None);
+        assert_eq!(
+            observe_and_check(&filter, "did:plc:b", &same_spam, at(2)),
+            None
+        );
+        assert_eq!(
+            observe_and_check(&filter, "did:plc:c", &spam, at(3)),
+            Some(SpamReason::DuplicateText)
+        );
+
+        for author in ["did:plc:a", "did:plc:b", "did:plc:c"] {
+            assert_eq!(
+                observe_and_check(&filter, author, &post("good morning"), at(4)),
+                None
+            );
+        }
+
+        // Long enough after the wave, the text is fair game again
+        assert_eq!(observe_and_check(&filter, "did:plc:d", &spam, at(70)), None);
+    }
+
+    #[test]
+    fn catches_link_only_posts() {
+        let filter = filter(SpamConfig {
+            reject_link_only_posts: true,
+            ..Default::default()
+        });
+
+        let link_only = PostFeatures {
+            links: vec!["https://example.com/offer".to_owned()],
+            ..post("https://example.com/offer")
+        };
+        assert_eq!(
+            observe_and_check(&filter, "did:plc:a", &link_only, at(0)),
+            Some(SpamReason::LinkOnly)
+        );
+
+        let card_only = PostFeatures {
+            external_link: Some(ExternalLink {
+                uri: "https://example.com/".to_owned(),
+                title: "Example".to_owned(),
+                description: String::new(),
+            }),
+            ..post("")
+        };
+        assert_eq!(
+            observe_and_check(&filter, "did:plc:a", &card_only, at(0)),
+            Some(SpamReason::LinkOnly)
+        );
+
+        let commented = PostFeatures {
+            links: vec!["https://example.com/article".to_owned()],
+            ..post("Good read: example.com/article")
+        };
+        assert_eq!(
+            observe_and_check(&filter, "did:plc:a", &commented, at(0)),
+            None
+        );
+    }
+
+    #[test]
+    fn checks_account_age() {
+        let filter = filter(SpamConfig {
+            min_account_age_hours: 24.0,
+            ..Default::default()
+        });
+
+        assert_eq!(
+            filter
+                .check_account_age(at(0), at(60))
+                .map(|verdict| verdict.reason),
+            Some(SpamReason::NewAccount)
+        );
+        assert_eq!(filter.check_account_age(at(0), at(24 * 60)), None);
+
+        let lenient = self::filter(Default::default());
+        assert_eq!(lenient.min_account_age(), None);
+        assert_eq!(lenient.check_account_age(at(0), at(1)), None);
+    }
+}